@@ -0,0 +1,9 @@
+-- Live websocket connections, used to tell which members are currently online
+CREATE TABLE member_connection (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    member_id UUID NOT NULL REFERENCES room_member(id) ON DELETE CASCADE,
+
+    connected_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX member_connection_member_id_idx ON member_connection (member_id);
@@ -0,0 +1,14 @@
+-- Persisted room events so reconnecting clients can replay what they missed
+ALTER TABLE room ADD COLUMN last_event_seq BIGINT NOT NULL DEFAULT 0;
+
+CREATE TABLE room_event (
+    room_id UUID NOT NULL REFERENCES room(id) ON DELETE CASCADE,
+    seq BIGINT NOT NULL, -- per-room sequence number, taken from room.last_event_seq
+    PRIMARY KEY (room_id, seq),
+
+    event JSONB NOT NULL,
+
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    CHECK (seq > 0)
+);
@@ -0,0 +1,5 @@
+-- Connections are kept alive by heartbeats, rows left behind by dead sockets expire on their own
+ALTER TABLE member_connection
+    ADD COLUMN expires_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL;
+
+CREATE INDEX member_connection_expires_at_idx ON member_connection (expires_at);
@@ -0,0 +1,14 @@
+-- Roles replace the single owner flag. Declared from least to most privileged so that
+-- roles can be compared directly.
+CREATE TYPE member_role AS ENUM (
+    'participant', -- takes part in the game
+    'organiser',   -- may also start the game and remove participants
+    'owner'        -- may also hand out roles and transfer ownership
+);
+
+ALTER TABLE room_member ADD COLUMN role member_role NOT NULL DEFAULT 'participant';
+UPDATE room_member SET role = 'owner' WHERE is_owner;
+ALTER TABLE room_member DROP COLUMN is_owner;
+
+-- every room has at most one owner
+CREATE UNIQUE INDEX room_member_single_owner_idx ON room_member (room_id) WHERE role = 'owner';
@@ -0,0 +1,47 @@
+-- the iteration was given up on after a phase ran past its deadline
+ALTER TYPE game_phase ADD VALUE 'aborted';
+
+-- What happens when a phase runs past its deadline.
+CREATE TYPE stall_policy AS ENUM (
+    'flag',  -- mark the iteration as stalled and leave it to the members
+    'abort'  -- abort the iteration
+);
+
+-- NULL timeouts mean the phase has no deadline
+ALTER TABLE room
+    ADD COLUMN santa_id_timeout_secs INTEGER CHECK (santa_id_timeout_secs > 0),
+    ADD COLUMN seed_reveal_timeout_secs INTEGER CHECK (seed_reveal_timeout_secs > 0),
+    ADD COLUMN verification_timeout_secs INTEGER CHECK (verification_timeout_secs > 0),
+    ADD COLUMN stall_policy stall_policy NOT NULL DEFAULT 'flag';
+
+ALTER TABLE game_iteration
+    ADD COLUMN phase_deadline TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN stalled_at TIMESTAMP WITH TIME ZONE; -- set once the deadline has been handled
+
+CREATE INDEX game_iteration_phase_deadline_idx ON game_iteration (phase_deadline)
+    WHERE stalled_at IS NULL;
+
+-- Every time an iteration enters a phase, start that phase's clock.
+CREATE OR REPLACE FUNCTION set_phase_deadline()
+    RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' OR NEW.phase IS DISTINCT FROM OLD.phase THEN
+        NEW.stalled_at = NULL;
+        NEW.phase_deadline = NOW() + make_interval(secs => (
+            SELECT CASE NEW.phase
+                WHEN 'santa_id' THEN santa_id_timeout_secs
+                WHEN 'seed_reveal' THEN seed_reveal_timeout_secs
+                WHEN 'verification' THEN verification_timeout_secs
+            END
+            FROM room
+            WHERE room.id = NEW.room_id
+        ));
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_set_game_iteration_phase_deadline
+    BEFORE INSERT OR UPDATE OF phase ON game_iteration
+    FOR EACH ROW
+EXECUTE PROCEDURE set_phase_deadline();
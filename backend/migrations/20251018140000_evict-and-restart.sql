@@ -0,0 +1,2 @@
+-- evict the members who did not respond and restart the game without them
+ALTER TYPE stall_policy ADD VALUE 'evict_and_restart';
@@ -0,0 +1,16 @@
+-- Recurring background jobs, leased to one backend instance at a time.
+CREATE TABLE scheduled_job (
+    name TEXT PRIMARY KEY,
+
+    next_run_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    locked_until TIMESTAMP WITH TIME ZONE, -- lease held by the instance running the job
+    locked_by UUID,
+
+    attempts INTEGER NOT NULL DEFAULT 0, -- consecutive failed runs, reset on success
+    last_error TEXT,
+
+    last_started_at TIMESTAMP WITH TIME ZONE,
+    last_finished_at TIMESTAMP WITH TIME ZONE,
+
+    CHECK (attempts >= 0)
+);
@@ -0,0 +1,4 @@
+-- lookups and reissues by member and token type
+CREATE INDEX token_member_id_type_idx ON token (member_id, type);
+-- expired token sweeps
+CREATE INDEX token_expires_at_idx ON token (expires_at);
@@ -0,0 +1,5 @@
+-- Tokens are now stored as SHA-256 digests. Existing rows hold plaintext tokens and cannot be
+-- converted without keeping them readable, so every member has to authenticate again.
+DELETE FROM token;
+
+ALTER TABLE token RENAME COLUMN token TO token_hash;
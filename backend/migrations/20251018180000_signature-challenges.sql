@@ -0,0 +1,2 @@
+-- nonces a member proves key ownership for by signing them, rather than by decrypting them
+ALTER TYPE token_type ADD VALUE 'signature_challenge';
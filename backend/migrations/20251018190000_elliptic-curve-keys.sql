@@ -0,0 +1,11 @@
+CREATE TYPE key_algorithm AS ENUM ('rsa', 'p256', 'x25519', 'ed25519');
+
+-- keys that can encrypt onion layers for each other
+CREATE TYPE key_suite AS ENUM ('rsa', 'p256', 'curve25519');
+
+-- only RSA keys were accepted so far
+ALTER TABLE room_member ADD COLUMN key_algorithm key_algorithm NOT NULL DEFAULT 'rsa';
+ALTER TABLE room_member ALTER COLUMN key_algorithm DROP DEFAULT;
+
+ALTER TABLE room ADD COLUMN key_suite key_suite NOT NULL DEFAULT 'rsa';
+ALTER TABLE room ALTER COLUMN key_suite DROP DEFAULT;
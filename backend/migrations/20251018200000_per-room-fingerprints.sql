@@ -0,0 +1,6 @@
+-- the same key may join several rooms, but only once per room
+ALTER TABLE room_member DROP CONSTRAINT room_member_fingerprint_key;
+ALTER TABLE room_member ADD CONSTRAINT room_member_room_id_fingerprint_key UNIQUE (room_id, fingerprint);
+
+-- challenges look members up by fingerprint alone
+CREATE INDEX room_member_fingerprint_idx ON room_member (fingerprint);
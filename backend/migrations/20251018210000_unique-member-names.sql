@@ -0,0 +1,35 @@
+-- Display names must be unique within a room, ignoring case.
+--
+-- Duplicates in rooms still in the lobby are numbered in join order. Rooms past the lobby keep
+-- their names, version 1 of the assignment protocol draws its targets from them, so their
+-- duplicates are exempted from the index instead.
+WITH duplicates AS (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY room_id, lower(name) ORDER BY joined_at, id) AS n
+    FROM room_member
+    WHERE NOT EXISTS(
+        SELECT 1
+        FROM game_iteration
+        WHERE game_iteration.room_id = room_member.room_id
+        AND game_iteration.phase <> 'lobby'
+    )
+)
+UPDATE room_member
+SET name = room_member.name || ' (' || duplicates.n || ')'
+FROM duplicates
+WHERE room_member.id = duplicates.id
+AND duplicates.n > 1;
+
+ALTER TABLE room_member ADD COLUMN legacy_duplicate_name BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE room_member
+SET legacy_duplicate_name = TRUE
+WHERE EXISTS(
+    SELECT 1
+    FROM room_member other
+    WHERE other.room_id = room_member.room_id
+    AND lower(other.name) = lower(room_member.name)
+    AND other.id <> room_member.id
+);
+
+CREATE UNIQUE INDEX room_member_room_id_name_key ON room_member (room_id, lower(name))
+    WHERE NOT legacy_duplicate_name;
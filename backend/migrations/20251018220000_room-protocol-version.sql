@@ -0,0 +1,4 @@
+-- Version of the assignment protocol the room derives its bijection with. Rooms created so far
+-- keep verifying under version 1, new rooms pick their version when created.
+ALTER TABLE room ADD COLUMN protocol_version SMALLINT NOT NULL DEFAULT 1;
+ALTER TABLE room ALTER COLUMN protocol_version DROP DEFAULT;
@@ -0,0 +1,5 @@
+-- Time of the connection's latest heartbeat, so presence can tell how recently a socket was alive
+ALTER TABLE member_connection
+    ADD COLUMN last_seen_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL;
+
+UPDATE member_connection SET last_seen_at = connected_at;
@@ -1,3 +1,4 @@
+use crate::features::auth::KeyAlgorithm;
 use config::Config;
 use serde::Deserialize;
 use std::fmt;
@@ -40,6 +41,9 @@ pub struct Settings {
     pub logging: LoggingSettings,
     pub postgresql: PostgreSQLSettings,
     pub auth: AuthSettings,
+    pub websocket: WebsocketSettings,
+    pub room: RoomSettings,
+    pub jobs: JobSettings,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -65,6 +69,66 @@ pub struct LoggingSettings {
 pub struct AuthSettings {
     pub session_cookie_name: String,
     pub session_cookie_secure: bool,
+    /// Sessions expire after this long without activity.
+    pub session_ttl_secs: u32,
+    /// Sessions are never renewed past this age, counted from sign-in.
+    pub session_max_lifetime_secs: u32,
+    pub ephemeral_token_ttl_secs: u32,
+    pub challenge_token_ttl_secs: u32,
+    pub key_policy: KeyPolicySettings,
+    pub token_gc_interval_secs: u32,
+    /// How long expired sessions are kept, so that clients can still be told the session
+    /// expired rather than that it does not exist.
+    pub expired_session_retention_secs: u32,
+}
+
+/// Which public keys members may register with.
+#[derive(Debug, Deserialize, Clone)]
+pub struct KeyPolicySettings {
+    pub allowed_algorithms: Vec<KeyAlgorithm>,
+    pub min_rsa_modulus_bits: u32,
+    pub allowed_rsa_exponents: Vec<u64>,
+    /// Limit on the base64 encoded key, checked before decoding it.
+    pub max_encoded_length: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebsocketSettings {
+    pub ping_interval_secs: u32,
+    pub idle_timeout_secs: u32,
+    pub max_connections_per_member: u32,
+    pub max_connections_per_room: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[allow(clippy::struct_field_names)]
+pub struct RoomSettings {
+    /// How long an owner may go without any activity before ownership passes to an active member.
+    pub owner_inactivity_timeout_secs: u32,
+    pub owner_check_interval_secs: u32,
+    pub deadline_check_interval_secs: u32,
+    pub connection_reap_interval_secs: u32,
+    pub abandoned_room_check_interval_secs: u32,
+    /// How long a room that never finished a game may go without any member activity before it
+    /// is deleted, absent means rooms are kept.
+    pub abandoned_room_ttl_secs: Option<u32>,
+    /// Phase timeouts for rooms that do not choose their own, absent means no deadline.
+    pub santa_id_timeout_secs: Option<u32>,
+    pub seed_reveal_timeout_secs: Option<u32>,
+    pub verification_timeout_secs: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[allow(clippy::struct_field_names)]
+pub struct JobSettings {
+    /// How often the runner checks for due jobs.
+    pub poll_interval_secs: u32,
+    /// How long a claimed job stays locked to one instance, must exceed the longest run.
+    pub lease_secs: u32,
+    pub retry_base_delay_secs: u32,
+    pub retry_max_delay_secs: u32,
+    /// How long running jobs get to finish on shutdown.
+    pub shutdown_timeout_secs: u32,
 }
 
 #[derive(Debug, Deserialize)]
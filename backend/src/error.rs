@@ -13,23 +13,31 @@ pub struct AppError {
     pub details: Option<Value>, // optional details
 }
 
-#[derive(Serialize)]
-struct ErrorResponse {
+/// The client facing shape of an [`AppError`], shared by HTTP responses and websocket frames.
+#[derive(Serialize, Debug)]
+pub struct ErrorResponse {
     code: String,
     message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     details: Option<Value>,
 }
 
+impl From<AppError> for ErrorResponse {
+    fn from(err: AppError) -> Self {
+        ErrorResponse {
+            code: err.code.to_string(),
+            message: err.message,
+            details: err.details,
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response<Body> {
-        let body = Json(ErrorResponse {
-            code: self.code.to_string(),
-            message: self.message,
-            details: self.details,
-        });
+        let status = self.status;
+        let body = Json(ErrorResponse::from(self));
 
-        (self.status, body).into_response()
+        (status, body).into_response()
     }
 }
 
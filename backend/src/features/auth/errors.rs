@@ -1,15 +1,61 @@
+use super::models::KeyAlgorithm;
 use crate::error::AppError;
 use axum::http::StatusCode;
 
 #[derive(Debug)]
 pub enum AuthError {
     InvalidPublicKey,
+    KeyPolicyViolation(KeyPolicyViolation),
     TokenGenerationFailed,
     ExpiredToken,
     MissingToken,
     MemberNotFound,
+    AmbiguousFingerprint(RoomChoices),
     TokenEncryptionFailed,
     InvalidToken,
+    SessionNotFound,
+    InvalidSignature,
+    UnsupportedChallengeMethod(KeyAlgorithm),
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct UnsupportedKey {
+    pub(crate) key_algorithm: KeyAlgorithm,
+}
+
+/// The rooms a key is a member of, for the client to pick from.
+#[derive(Debug, serde::Serialize)]
+pub struct RoomChoices {
+    pub(crate) rooms: Vec<RoomChoice>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct RoomChoice {
+    pub(crate) room_id: String,
+    pub(crate) name: String,
+}
+
+/// The key policy rule a public key failed.
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum KeyPolicyViolation {
+    MaxEncodedLength {
+        limit: u32,
+        actual: usize,
+    },
+    AllowedAlgorithms {
+        allowed: Vec<KeyAlgorithm>,
+        actual: KeyAlgorithm,
+    },
+    MinRsaModulusBits {
+        minimum: u32,
+        actual: usize,
+    },
+    AllowedRsaExponents {
+        allowed: Vec<u64>,
+        /// Hex encoded, exponents need not fit in 64 bits.
+        actual: String,
+    },
 }
 
 impl From<AuthError> for AppError {
@@ -20,6 +66,12 @@ impl From<AuthError> for AppError {
                 "The provided public key is invalid or malformed.",
                 StatusCode::BAD_REQUEST,
             ),
+            AuthError::KeyPolicyViolation(violation) => AppError::new(
+                "INVALID_PUBLIC_KEY",
+                "The provided public key is not allowed by the key policy.",
+                StatusCode::BAD_REQUEST,
+            )
+            .with_details(violation),
             AuthError::TokenGenerationFailed => AppError::new(
                 "TOKEN_GENERATION_FAILED",
                 "Failed to generate a secure token. Please try again later.",
@@ -40,6 +92,12 @@ impl From<AuthError> for AppError {
                 "No member found with the provided fingerprint.",
                 StatusCode::NOT_FOUND,
             ),
+            AuthError::AmbiguousFingerprint(rooms) => AppError::new(
+                "AMBIGUOUS_FINGERPRINT",
+                "This key is a member of several rooms. Specify which room to sign in to.",
+                StatusCode::CONFLICT,
+            )
+            .with_details(rooms),
             AuthError::TokenEncryptionFailed => AppError::new(
                 "TOKEN_ENCRYPTION_FAILED",
                 "Failed to encrypt the token. Please try again later.",
@@ -50,6 +108,22 @@ impl From<AuthError> for AppError {
                 "The provided token is invalid or has expired.",
                 StatusCode::BAD_REQUEST,
             ),
+            AuthError::InvalidSignature => AppError::new(
+                "INVALID_SIGNATURE",
+                "The signature does not match the challenge and your public key.",
+                StatusCode::BAD_REQUEST,
+            ),
+            AuthError::UnsupportedChallengeMethod(key_algorithm) => AppError::new(
+                "UNSUPPORTED_CHALLENGE_METHOD",
+                "Your key cannot be used with this challenge method.",
+                StatusCode::BAD_REQUEST,
+            )
+            .with_details(UnsupportedKey { key_algorithm }),
+            AuthError::SessionNotFound => AppError::new(
+                "SESSION_NOT_FOUND",
+                "No session with this ID exists for your account.",
+                StatusCode::NOT_FOUND,
+            ),
         }
     }
 }
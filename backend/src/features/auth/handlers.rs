@@ -1,10 +1,10 @@
 use super::schemas;
 use super::{middleware::Session, service};
 use crate::error::AppError;
-use crate::features::auth::utils::new_session_cookie;
+use crate::features::auth::utils::{new_session_cookie, session_removal_cookie};
 use crate::state::SharedState;
 use axum::Json;
-use axum::extract::{ConnectInfo, State};
+use axum::extract::{ConnectInfo, Path, State};
 use axum::http::{HeaderMap, StatusCode};
 use axum::response::IntoResponse;
 use axum_extra::extract::CookieJar;
@@ -19,9 +19,16 @@ pub async fn create_challenge(
     let user_agent = headers.get("User-Agent").and_then(|h| h.to_str().ok());
     let ip_address = Some(addr.ip());
 
-    let challenge_token =
-        service::create_challenge_token(&state.db, &request.fingerprint, user_agent, ip_address)
-            .await?;
+    let challenge_token = service::create_challenge_token(
+        &state.db,
+        &state.config.auth,
+        &request.fingerprint,
+        request.room_id.as_deref(),
+        request.method,
+        user_agent,
+        ip_address,
+    )
+    .await?;
 
     Ok((
         StatusCode::CREATED,
@@ -41,16 +48,18 @@ pub async fn verify_challenge(
     let user_agent = headers.get("User-Agent").and_then(|h| h.to_str().ok());
     let ip_address = Some(addr.ip());
 
-    let session_token = service::exchange_challenge_for_session(
+    let (session_token, expires_at) = service::exchange_challenge_for_session(
         &state.db,
+        &state.config.auth,
         &request.token,
         &request.fingerprint,
+        request.signature.as_deref(),
         user_agent,
         ip_address,
     )
     .await?;
 
-    let session_cookie = new_session_cookie(&state.config.auth, &session_token);
+    let session_cookie = new_session_cookie(&state.config.auth, &session_token, expires_at);
     Ok((StatusCode::CREATED, cookies.add(session_cookie)))
 }
 
@@ -63,9 +72,14 @@ pub async fn create_ephemeral_token(
     let user_agent = headers.get("User-Agent").and_then(|h| h.to_str().ok());
     let ip_address = Some(addr.ip());
 
-    let ephemeral_token =
-        service::create_ephemeral_token(&state.db, session.member_id, user_agent, ip_address)
-            .await?;
+    let ephemeral_token = service::create_ephemeral_token(
+        &state.db,
+        &state.config.auth,
+        session.member_id,
+        user_agent,
+        ip_address,
+    )
+    .await?;
 
     Ok((
         StatusCode::CREATED,
@@ -73,6 +87,39 @@ pub async fn create_ephemeral_token(
     ))
 }
 
+pub async fn list_sessions(
+    State(state): State<SharedState>,
+    Session(session): Session,
+) -> Result<impl IntoResponse, AppError> {
+    let sessions = service::list_sessions(&state.db, session.member_id, session.id).await?;
+    Ok(Json(schemas::SessionListResponse { sessions }))
+}
+
+pub async fn revoke_session(
+    State(state): State<SharedState>,
+    Session(session): Session,
+    Path(session_id): Path<uuid::Uuid>,
+    cookies: CookieJar,
+) -> Result<impl IntoResponse, AppError> {
+    service::revoke_session(&state.db, session.member_id, session_id).await?;
+
+    // Revoking the current session is a plain logout for this device
+    if session_id == session.id {
+        let removal_cookie = session_removal_cookie(&state.config.auth);
+        return Ok((StatusCode::NO_CONTENT, cookies.remove(removal_cookie)));
+    }
+
+    Ok((StatusCode::NO_CONTENT, cookies))
+}
+
+pub async fn revoke_other_sessions(
+    State(state): State<SharedState>,
+    Session(session): Session,
+) -> Result<impl IntoResponse, AppError> {
+    let revoked = service::revoke_other_sessions(&state.db, session.member_id, session.id).await?;
+    Ok(Json(schemas::RevokedSessionsResponse { revoked }))
+}
+
 pub async fn logout(
     State(state): State<SharedState>,
     Session(session): Session,
@@ -80,6 +127,6 @@ pub async fn logout(
 ) -> Result<impl IntoResponse, AppError> {
     service::logout(&state.db, session.member_id).await?;
 
-    let removal_cookie = new_session_cookie(&state.config.auth, "");
+    let removal_cookie = session_removal_cookie(&state.config.auth);
     Ok((StatusCode::NO_CONTENT, cookies.remove(removal_cookie)))
 }
@@ -1,3 +1,5 @@
 mod session_extractor;
+mod session_renewal;
 
 pub use session_extractor::Session;
+pub use session_renewal::refresh_session_cookie;
@@ -1,6 +1,8 @@
 use super::super::errors::AuthError;
 use super::super::models;
 use super::super::queries;
+use super::super::utils::{cryptography, new_session_cookie};
+use super::session_renewal::RenewedSessionCookie;
 use crate::error::AppError;
 use crate::state::SharedState;
 use axum::extract::FromRequestParts;
@@ -11,19 +13,27 @@ use axum_extra::extract::CookieJar;
 #[derive(Debug)]
 pub struct Session(pub models::Token);
 
-fn extract_token(parts: &mut Parts, state: &SharedState) -> Result<String, AppError> {
+enum TokenSource {
+    Cookie,
+    Header,
+}
+
+fn extract_token(
+    parts: &mut Parts,
+    state: &SharedState,
+) -> Result<(String, TokenSource), AppError> {
     // 1. Try cookie first
     let cookies = CookieJar::from_headers(&parts.headers);
     if let Some(cookie) = cookies.get(&state.config.auth.session_cookie_name) {
-        return Ok(cookie.value().to_string());
+        return Ok((cookie.value().to_string(), TokenSource::Cookie));
     }
 
     // 2. Try Authorization header
-    if let Some(auth_header) = parts.headers.get(AUTHORIZATION) {
-        if let Ok(auth_str) = auth_header.to_str() {
-            let token = auth_str.trim_start_matches("Bearer ");
-            return Ok(token.to_string());
-        }
+    if let Some(auth_header) = parts.headers.get(AUTHORIZATION)
+        && let Ok(auth_str) = auth_header.to_str()
+    {
+        let token = auth_str.trim_start_matches("Bearer ");
+        return Ok((token.to_string(), TokenSource::Header));
     }
 
     // 3. If neither is found, return an error
@@ -38,19 +48,33 @@ impl FromRequestParts<SharedState> for Session {
         state: &SharedState,
     ) -> Result<Self, Self::Rejection> {
         // Get token
-        let token = extract_token(parts, state)?;
+        let (token, source) = extract_token(parts, state)?;
 
-        // Get session from token
-        let session = queries::get_session_token_and_update_access_time(&state.db, &token)
-            .await?
-            .map(Self)
-            .ok_or_else(|| AppError::from(AuthError::ExpiredToken))?;
+        // Get session from token, renewing it if it is about to expire
+        let settings = &state.config.auth;
+        let token_hash = cryptography::hash_token(&token);
+        let (session, renewed) = queries::get_session_token_and_update_access_time(
+            &state.db,
+            &token_hash,
+            f64::from(settings.session_ttl_secs),
+            f64::from(settings.session_max_lifetime_secs),
+        )
+        .await?
+        .ok_or_else(|| AppError::from(AuthError::ExpiredToken))?;
 
         // Check if session is expired
-        if session.0.expires_at < chrono::Utc::now() {
+        if session.expires_at < chrono::Utc::now() {
             return Err(AppError::from(AuthError::ExpiredToken));
         }
 
-        Ok(session)
+        // Header clients manage the token themselves, only the cookie needs refreshing
+        if renewed
+            && matches!(source, TokenSource::Cookie)
+            && let Some(slot) = parts.extensions.get::<RenewedSessionCookie>()
+        {
+            slot.set(new_session_cookie(settings, &token, session.expires_at));
+        }
+
+        Ok(Self(session))
     }
 }
@@ -0,0 +1,34 @@
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::http::header::SET_COOKIE;
+use axum::middleware::Next;
+use axum::response::Response;
+use axum_extra::extract::cookie::Cookie;
+use std::sync::{Arc, OnceLock};
+
+/// Slot the [`super::Session`] extractor fills in when it renews a cookie-based session.
+#[derive(Clone, Default)]
+pub(crate) struct RenewedSessionCookie(Arc<OnceLock<Cookie<'static>>>);
+
+impl RenewedSessionCookie {
+    pub(crate) fn set(&self, cookie: Cookie<'static>) {
+        // A request only renews its session once, later attempts are no-ops
+        let _ = self.0.set(cookie);
+    }
+}
+
+/// Sends the refreshed session cookie along with the response when the session was renewed.
+pub async fn refresh_session_cookie(mut request: Request, next: Next) -> Response {
+    let renewed = RenewedSessionCookie::default();
+    request.extensions_mut().insert(renewed.clone());
+
+    let mut response = next.run(request).await;
+
+    if let Some(cookie) = renewed.0.get()
+        && let Ok(value) = HeaderValue::from_str(&cookie.encoded().to_string())
+    {
+        response.headers_mut().append(SET_COOKIE, value);
+    }
+
+    response
+}
@@ -1,5 +1,8 @@
+use crate::config::AuthSettings;
+use crate::jobs::Job;
 use crate::state::SharedState;
-use axum::routing::post;
+use axum::routing::{delete, get, post};
+use std::time::Duration;
 
 mod errors;
 mod handlers;
@@ -10,11 +13,31 @@ pub(crate) mod schemas;
 pub(crate) mod service;
 pub(crate) mod utils;
 
-pub use middleware::Session;
+pub use middleware::{Session, refresh_session_cookie};
+pub use models::{KeyAlgorithm, KeySuite, SessionState};
+
+/// Recurring auth chores, run by the job runner.
+pub(crate) fn jobs(settings: &AuthSettings) -> Vec<Job> {
+    vec![Job::new(
+        "auth.delete_expired_tokens",
+        Duration::from_secs(settings.token_gc_interval_secs.into()),
+        |state| {
+            Box::pin(
+                async move { service::delete_expired_tokens(&state.db, &state.config.auth).await },
+            )
+        },
+    )]
+}
 
 pub fn build_router() -> axum::Router<SharedState> {
     axum::Router::new()
         .route("/logout", post(handlers::logout))
+        .route("/sessions", get(handlers::list_sessions))
+        .route("/sessions/{session_id}", delete(handlers::revoke_session))
+        .route(
+            "/sessions/revoke-others",
+            post(handlers::revoke_other_sessions),
+        )
         .route("/challenge", post(handlers::create_challenge))
         .route("/challenge/verify", post(handlers::verify_challenge))
         .route("/ephemeral", post(handlers::create_ephemeral_token))
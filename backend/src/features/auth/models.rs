@@ -1,12 +1,66 @@
 use sqlx::types::ipnet::IpNet;
 use std::fmt::Debug;
 
-#[derive(sqlx::Type)]
+#[derive(sqlx::Type, Debug)]
 #[sqlx(type_name = "token_type", rename_all = "lowercase")]
 pub enum TokenType {
     Session,
     Ephemeral,
     Challenge,
+    #[sqlx(rename = "signature_challenge")]
+    SignatureChallenge,
+}
+
+/// Algorithm of a member's public key.
+#[derive(sqlx::Type, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[sqlx(type_name = "key_algorithm", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum KeyAlgorithm {
+    Rsa,
+    P256,
+    X25519,
+    Ed25519,
+}
+
+impl KeyAlgorithm {
+    /// The suite onion layers for a key of this algorithm are encrypted with.
+    ///
+    /// Ed25519 keys share the Curve25519 suite with X25519, clients convert them for key
+    /// agreement.
+    pub fn suite(self) -> KeySuite {
+        match self {
+            Self::Rsa => KeySuite::Rsa,
+            Self::P256 => KeySuite::P256,
+            Self::X25519 | Self::Ed25519 => KeySuite::Curve25519,
+        }
+    }
+}
+
+/// Keys that can encrypt onion layers for each other. All members of a room share one suite.
+#[derive(sqlx::Type, serde::Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[sqlx(type_name = "key_suite", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum KeySuite {
+    Rsa,
+    P256,
+    Curve25519,
+}
+
+/// State of a member's session tokens.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SessionState {
+    Active,
+    Expired,
+    /// The member holds no session tokens, they logged out or were removed.
+    LoggedOut,
+}
+
+/// A room the holder of a key is a member of.
+pub struct Membership {
+    pub member_id: uuid::Uuid,
+    pub public_key: Vec<u8>,
+    pub room_code: String,
+    pub room_name: String,
 }
 
 pub struct Token {
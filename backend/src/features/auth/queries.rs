@@ -1,33 +1,30 @@
-use super::models::{Token, TokenType};
+use super::models::{Membership, Token, TokenType};
 use sqlx::PgPool;
 use sqlx::types::ipnet::IpNet;
 use std::net::IpAddr;
 use uuid::Uuid;
 
-/// Creates a new token for a member.
+/// Creates a new token for a member, stored by its hash.
 ///
-/// This function also deletes any pre-existing tokens of the same type for the member.
+/// Tokens of every type accumulate, so a login or socket handshake on one device does not
+/// invalidate another's. Short-lived tokens are removed on use or by the expiry sweep.
 pub async fn new_token(
     pool: &PgPool,
     member_id: Uuid,
     token_type: &TokenType,
-    token: &str,
+    token_hash: &str,
     expires_at: &chrono::DateTime<chrono::Utc>,
     user_agent: Option<&str>,
     ip_address: Option<IpAddr>,
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
-        WITH deleted AS (
-            DELETE FROM token
-            WHERE member_id = $1 AND type = $2
-        )
-        INSERT INTO token (member_id, type, token, expires_at, user_agent, ip_address)
+        INSERT INTO token (member_id, type, token_hash, expires_at, user_agent, ip_address)
         VALUES ($1, $2, $3, $4, $5, $6)
         "#,
         member_id,
         token_type as &TokenType,
-        token,
+        token_hash,
         expires_at,
         user_agent,
         ip_address.map(IpNet::from)
@@ -38,22 +35,153 @@ pub async fn new_token(
     Ok(())
 }
 
+/// Deletes expired challenge and ephemeral tokens, and sessions that expired more than
+/// `session_retention_secs` ago.
+///
+/// Returns the number of tokens deleted per type.
+pub async fn delete_expired_tokens(
+    pool: &PgPool,
+    session_retention_secs: f64,
+) -> Result<Vec<(TokenType, i64)>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        WITH deleted AS (
+            DELETE FROM token
+            WHERE (type = 'session' AND expires_at < NOW() - make_interval(secs => $1))
+            OR (type <> 'session' AND expires_at < NOW())
+            RETURNING type
+        )
+        SELECT type AS "token_type!: TokenType", COUNT(*) AS "count!"
+        FROM deleted
+        GROUP BY type
+        "#,
+        session_retention_secs
+    )
+    .fetch_all(pool)
+    .await
+    .map(|rows| {
+        rows.into_iter()
+            .map(|row| (row.token_type, row.count))
+            .collect()
+    })
+}
+
+/// Fetches a session and records the access.
+///
+/// Once less than half of `ttl_secs` remains, an unexpired session is renewed to expire
+/// `ttl_secs` from now, but never later than `max_lifetime_secs` after it was created. The
+/// returned flag tells whether the session was renewed.
 pub async fn get_session_token_and_update_access_time(
     pool: &PgPool,
-    token: &str,
-) -> Result<Option<Token>, sqlx::Error> {
-    sqlx::query_as!(
-        Token,
+    token_hash: &str,
+    ttl_secs: f64,
+    max_lifetime_secs: f64,
+) -> Result<Option<(Token, bool)>, sqlx::Error> {
+    sqlx::query!(
         r#"
+        WITH current AS (
+            SELECT id, expires_at
+            FROM token
+            WHERE token_hash = $1 AND type = 'session'
+            FOR UPDATE
+        )
         UPDATE token
-        SET last_seen_at = NOW()
-        WHERE token = $1 AND type = 'session'
-        RETURNING id, member_id, created_at, expires_at, last_seen_at, user_agent, ip_address
+        SET last_seen_at = NOW(),
+            expires_at = CASE
+                WHEN token.expires_at > NOW()
+                AND token.expires_at < NOW() + make_interval(secs => $2::float8 / 2)
+                THEN GREATEST(
+                    token.expires_at,
+                    LEAST(
+                        NOW() + make_interval(secs => $2),
+                        token.created_at + make_interval(secs => $3)
+                    )
+                )
+                ELSE token.expires_at
+            END
+        FROM current
+        WHERE token.id = current.id
+        RETURNING token.id, token.member_id, token.created_at, token.expires_at,
+            token.last_seen_at, token.user_agent, token.ip_address,
+            token.expires_at <> current.expires_at AS "renewed!"
         "#,
-        token
+        token_hash,
+        ttl_secs,
+        max_lifetime_secs
     )
     .fetch_optional(pool)
     .await
+    .map(|row| {
+        row.map(|r| {
+            let token = Token {
+                id: r.id,
+                member_id: r.member_id,
+                created_at: r.created_at,
+                expires_at: r.expires_at,
+                last_seen_at: r.last_seen_at,
+                user_agent: r.user_agent,
+                ip_address: r.ip_address,
+            };
+            (token, r.renewed)
+        })
+    })
+}
+
+/// Lists the member's unexpired sessions, most recently used first.
+pub async fn get_member_sessions(
+    pool: &PgPool,
+    member_id: Uuid,
+) -> Result<Vec<Token>, sqlx::Error> {
+    sqlx::query_as!(
+        Token,
+        r#"
+        SELECT id, member_id, created_at, expires_at, last_seen_at, user_agent, ip_address
+        FROM token
+        WHERE member_id = $1 AND type = 'session' AND expires_at > NOW()
+        ORDER BY last_seen_at DESC
+        "#,
+        member_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Deletes one of the member's sessions. Returns whether it existed.
+pub async fn delete_session(
+    pool: &PgPool,
+    member_id: Uuid,
+    session_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM token
+        WHERE id = $1 AND member_id = $2 AND type = 'session'
+        "#,
+        session_id,
+        member_id
+    )
+    .execute(pool)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+/// Deletes all of the member's sessions except `keep_session_id`.
+pub async fn delete_other_sessions(
+    pool: &PgPool,
+    member_id: Uuid,
+    keep_session_id: Uuid,
+) -> Result<u64, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM token
+        WHERE member_id = $1 AND type = 'session' AND id <> $2
+        "#,
+        member_id,
+        keep_session_id
+    )
+    .execute(pool)
+    .await
+    .map(|result| result.rows_affected())
 }
 
 pub async fn delete_all_tokens(pool: &PgPool, member_id: Uuid) -> Result<(), sqlx::Error> {
@@ -70,27 +198,72 @@ pub async fn delete_all_tokens(pool: &PgPool, member_id: Uuid) -> Result<(), sql
     Ok(())
 }
 
-pub async fn get_member_by_fingerprint(
+/// Fetches the latest expiry among the member's session tokens.
+pub async fn get_latest_session_expiry(
+    pool: &PgPool,
+    member_id: Uuid,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT MAX(expires_at) AS expires_at
+        FROM token
+        WHERE member_id = $1 AND type = 'session'
+        "#,
+        member_id
+    )
+    .fetch_one(pool)
+    .await
+    .map(|row| row.expires_at)
+}
+
+/// Fetches the memberships of the key with `fingerprint`, optionally only the one in the room
+/// with `room_code`.
+pub async fn get_members_by_fingerprint(
     pool: &PgPool,
     fingerprint: &str,
-) -> Result<Option<(Uuid, Vec<u8>)>, sqlx::Error> {
+    room_code: Option<&str>,
+) -> Result<Vec<Membership>, sqlx::Error> {
+    sqlx::query_as!(
+        Membership,
+        r#"
+        SELECT room_member.id AS member_id, room_member.public_key,
+            room.join_code AS room_code, room.name AS room_name
+        FROM room_member
+        JOIN room ON room.id = room_member.room_id
+        WHERE room_member.fingerprint = $1
+        AND ($2::TEXT IS NULL OR room.join_code = $2)
+        ORDER BY room_member.joined_at
+        "#,
+        fingerprint,
+        room_code
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_member_public_key(
+    pool: &PgPool,
+    member_id: Uuid,
+) -> Result<Option<Vec<u8>>, sqlx::Error> {
     sqlx::query!(
         r#"
-        SELECT id, public_key
+        SELECT public_key
         FROM room_member
-        WHERE fingerprint = $1
+        WHERE id = $1
         "#,
-        fingerprint
+        member_id
     )
     .fetch_optional(pool)
     .await
-    .map(|row| row.map(|r| (r.id, r.public_key)))
+    .map(|row| row.map(|r| r.public_key))
 }
 
+/// Consumes a challenge of the given type issued to the member with `fingerprint`.
 pub async fn get_and_delete_challenge_token_for_fingerprint(
     pool: &PgPool,
     fingerprint: &str,
-    token: &str,
+    token_type: &TokenType,
+    token_hash: &str,
 ) -> Result<Option<Token>, sqlx::Error> {
     sqlx::query_as!(
         Token,
@@ -99,12 +272,36 @@ pub async fn get_and_delete_challenge_token_for_fingerprint(
         USING room_member
         WHERE member_id = room_member.id
         AND room_member.fingerprint = $1
-        AND token.type = 'challenge' 
-        AND token.token = $2
+        AND token.type = $2
+        AND token.token_hash = $3
         RETURNING token.id, token.member_id, token.created_at, token.expires_at, token.last_seen_at, token.user_agent, token.ip_address
         "#,
         fingerprint,
-        token
+        token_type as &TokenType,
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn get_ephemeral_token_by_room_code(
+    pool: &PgPool,
+    room_code: &str,
+    token_hash: &str,
+) -> Result<Option<Token>, sqlx::Error> {
+    sqlx::query_as!(
+        Token,
+        r#"
+        SELECT token.id, token.member_id, token.created_at, token.expires_at, token.last_seen_at, token.user_agent, token.ip_address
+        FROM token
+        JOIN room_member rm ON token.member_id = rm.id
+        JOIN room r ON rm.room_id = r.id
+        WHERE r.join_code = $1
+        AND token.token_hash = $2
+        AND token.type = 'ephemeral'
+        "#,
+        room_code,
+        token_hash
     )
     .fetch_optional(pool)
     .await
@@ -113,7 +310,7 @@ pub async fn get_and_delete_challenge_token_for_fingerprint(
 pub async fn get_and_delete_ephemeral_token_by_room_code(
     pool: &PgPool,
     room_code: &str,
-    token: &str,
+    token_hash: &str,
 ) -> Result<Option<Token>, sqlx::Error> {
     sqlx::query_as!(
         Token,
@@ -123,12 +320,12 @@ pub async fn get_and_delete_ephemeral_token_by_room_code(
         WHERE token.member_id = rm.id
         AND rm.room_id = r.id
         AND r.join_code = $1
-        AND token.token = $2
+        AND token.token_hash = $2
         AND token.type = 'ephemeral'
         RETURNING token.id, token.member_id, token.created_at, token.expires_at, token.last_seen_at, token.user_agent, token.ip_address
         "#,
         room_code,
-        token
+        token_hash
     )
     .fetch_optional(pool)
     .await
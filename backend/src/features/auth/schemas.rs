@@ -1,8 +1,27 @@
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 
 #[derive(Deserialize)]
 pub struct CreateChallengeTokenRequest {
     pub fingerprint: String,
+    /// Join code of the room to sign in to, required when the key is in several rooms.
+    pub room_id: Option<String>,
+    #[serde(default)]
+    pub method: ChallengeMethod,
+}
+
+/// How the member proves they hold the private key.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengeMethod {
+    /// The challenge is encrypted with RSA-OAEP/SHA-512, the member sends back the plaintext.
+    #[default]
+    Encryption,
+    /// The challenge is sent in plaintext, the member sends it back along with a signature.
+    Signature,
+    /// The challenge is a one-time X25519 public key, the member derives the token from the
+    /// secret it agrees on with their key and sends the token back.
+    KeyAgreement,
 }
 
 #[derive(Serialize)]
@@ -14,9 +33,33 @@ pub struct ChallengeResponse {
 pub struct ChallengeVerificationRequest {
     pub token: String,
     pub fingerprint: String,
+    /// Base64 encoded signature over the challenge, for the `signature` method.
+    pub signature: Option<String>,
 }
 
 #[derive(Serialize)]
 pub struct EphemeralTokenResponse {
     pub ephemeral_token: String,
 }
+
+#[derive(Serialize)]
+pub struct SessionListResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Serialize)]
+pub struct SessionResponse {
+    pub session_id: uuid::Uuid,
+    /// Whether this is the session making the request.
+    pub current: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_seen_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<IpAddr>,
+}
+
+#[derive(Serialize)]
+pub struct RevokedSessionsResponse {
+    pub revoked: u64,
+}
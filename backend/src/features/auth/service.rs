@@ -1,47 +1,61 @@
-use super::{errors::AuthError, models::TokenType, queries, utils::cryptography};
+use super::{
+    errors::{AuthError, RoomChoice, RoomChoices},
+    models::{Membership, SessionState, TokenType},
+    queries,
+    schemas::{ChallengeMethod, SessionResponse},
+    utils::{cryptography, public_key::PublicKey},
+};
+use crate::config::AuthSettings;
 use crate::error::AppError;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
 use std::net::IpAddr;
+use tracing::info;
 
-static SESSION_TOKEN_DURATION: chrono::Duration = chrono::Duration::hours(1);
-static EPHEMERAL_TOKEN_DURATION: chrono::Duration = chrono::Duration::minutes(2);
-static CHALLENGE_TOKEN_DURATION: chrono::Duration = chrono::Duration::minutes(2);
+/// Prefix of signature challenges, separating them from anything else the key might sign.
+pub const SIGNATURE_CHALLENGE_CONTEXT: &str = "chimney-login-v1";
 
+/// Creates a session token, returning it along with its expiry.
 pub async fn create_session_token(
     pool: &sqlx::PgPool,
+    settings: &AuthSettings,
     member_id: uuid::Uuid,
     user_agent: Option<&str>,
     ip_address: Option<IpAddr>,
-) -> Result<String, AppError> {
+) -> Result<(String, chrono::DateTime<chrono::Utc>), AppError> {
     let token = cryptography::generate_secure_token()?;
-    let expiration = chrono::Utc::now() + SESSION_TOKEN_DURATION;
+    let expiration =
+        chrono::Utc::now() + chrono::Duration::seconds(settings.session_ttl_secs.into());
 
     queries::new_token(
         pool,
         member_id,
         &TokenType::Session,
-        &token,
+        &cryptography::hash_token(&token),
         &expiration,
         user_agent,
         ip_address,
     )
     .await?;
-    Ok(token)
+    Ok((token, expiration))
 }
 
 pub async fn create_ephemeral_token(
     pool: &sqlx::PgPool,
+    settings: &AuthSettings,
     member_id: uuid::Uuid,
     user_agent: Option<&str>,
     ip_address: Option<IpAddr>,
 ) -> Result<String, AppError> {
     let token = cryptography::generate_secure_token()?;
-    let expiration = chrono::Utc::now() + EPHEMERAL_TOKEN_DURATION;
+    let expiration =
+        chrono::Utc::now() + chrono::Duration::seconds(settings.ephemeral_token_ttl_secs.into());
 
     queries::new_token(
         pool,
         member_id,
         &TokenType::Ephemeral,
-        &token,
+        &cryptography::hash_token(&token),
         &expiration,
         user_agent,
         ip_address,
@@ -50,25 +64,68 @@ pub async fn create_ephemeral_token(
     Ok(token)
 }
 
+/// Issues a challenge proving ownership of the key with `fingerprint`.
+///
+/// A key can be a member of several rooms, each membership signs in separately. Without a
+/// `room_code` the key must be in exactly one room.
+///
+/// Encryption challenges are returned encrypted to the member's key. Signature challenges are
+/// returned in plaintext, prefixed with [`SIGNATURE_CHALLENGE_CONTEXT`] and the fingerprint so
+/// a signature over one cannot be passed off as anything else.
 pub async fn create_challenge_token(
     pool: &sqlx::PgPool,
+    settings: &AuthSettings,
     fingerprint: &str,
+    room_code: Option<&str>,
+    method: ChallengeMethod,
     user_agent: Option<&str>,
     ip_address: Option<IpAddr>,
 ) -> Result<String, AppError> {
-    let (member_id, public_key) = queries::get_member_by_fingerprint(pool, fingerprint)
-        .await?
-        .ok_or(AuthError::MemberNotFound)?;
+    let mut memberships = queries::get_members_by_fingerprint(pool, fingerprint, room_code).await?;
+    if memberships.len() > 1 {
+        let rooms = memberships
+            .into_iter()
+            .map(|membership| RoomChoice {
+                room_id: membership.room_code,
+                name: membership.room_name,
+            })
+            .collect();
+        return Err(AuthError::AmbiguousFingerprint(RoomChoices { rooms }).into());
+    }
+    let Membership {
+        member_id,
+        public_key,
+        ..
+    } = memberships.pop().ok_or(AuthError::MemberNotFound)?;
+
+    let nonce = cryptography::generate_secure_token()?;
+    let (token_type, raw_token, token) = match method {
+        ChallengeMethod::Encryption => {
+            let token = cryptography::encrypt_challenge_token(&nonce, &public_key)?;
+            (TokenType::Challenge, nonce, token)
+        }
+        ChallengeMethod::Signature => {
+            let key = PublicKey::from_der(&public_key)?;
+            if !key.can_sign() {
+                return Err(AuthError::UnsupportedChallengeMethod(key.algorithm()).into());
+            }
 
-    let raw_token = cryptography::generate_secure_token()?;
-    let token = cryptography::encrypt_challenge_token(&raw_token, &public_key)?;
+            let token = format!("{SIGNATURE_CHALLENGE_CONTEXT}:{fingerprint}:{nonce}");
+            (TokenType::SignatureChallenge, token.clone(), token)
+        }
+        ChallengeMethod::KeyAgreement => {
+            let (token, server_key) = cryptography::key_agreement_challenge(&public_key)?;
+            (TokenType::Challenge, token, server_key)
+        }
+    };
 
-    let expiration = chrono::Utc::now() + CHALLENGE_TOKEN_DURATION;
+    let expiration =
+        chrono::Utc::now() + chrono::Duration::seconds(settings.challenge_token_ttl_secs.into());
     queries::new_token(
         pool,
         member_id,
-        &TokenType::Challenge,
-        &raw_token,
+        &token_type,
+        &cryptography::hash_token(&raw_token),
         &expiration,
         user_agent,
         ip_address,
@@ -79,31 +136,78 @@ pub async fn create_challenge_token(
 
 pub async fn exchange_challenge_for_session(
     pool: &sqlx::PgPool,
+    settings: &AuthSettings,
     token: &str,
     fingerprint: &str,
+    signature: Option<&str>,
     user_agent: Option<&str>,
     ip_address: Option<IpAddr>,
-) -> Result<String, AppError> {
-    let challenge_token =
-        queries::get_and_delete_challenge_token_for_fingerprint(pool, fingerprint, token)
-            .await?
-            .ok_or(AuthError::InvalidToken)?;
+) -> Result<(String, chrono::DateTime<chrono::Utc>), AppError> {
+    let token_type = match signature {
+        Some(_) => TokenType::SignatureChallenge,
+        None => TokenType::Challenge,
+    };
+
+    let challenge_token = queries::get_and_delete_challenge_token_for_fingerprint(
+        pool,
+        fingerprint,
+        &token_type,
+        &cryptography::hash_token(token),
+    )
+    .await?
+    .ok_or(AuthError::InvalidToken)?;
 
     if challenge_token.expires_at < chrono::Utc::now() {
         return Err(AuthError::InvalidToken.into());
     }
 
-    let session_token =
-        create_session_token(pool, challenge_token.member_id, user_agent, ip_address).await?;
-    Ok(session_token)
+    if let Some(signature) = signature {
+        let signature = BASE64_STANDARD
+            .decode(signature)
+            .or(Err(AuthError::InvalidSignature))?;
+        let public_key = queries::get_member_public_key(pool, challenge_token.member_id)
+            .await?
+            .ok_or(AuthError::MemberNotFound)?;
+
+        PublicKey::from_der(&public_key)?.verify(token.as_bytes(), &signature)?;
+    }
+
+    create_session_token(
+        pool,
+        settings,
+        challenge_token.member_id,
+        user_agent,
+        ip_address,
+    )
+    .await
+}
+
+/// Looks up the member a websocket token was issued to without using the token up.
+pub async fn peek_websocket_token(
+    pool: &sqlx::PgPool,
+    token: &str,
+    room_code: &str,
+) -> Result<uuid::Uuid, AppError> {
+    let token_hash = cryptography::hash_token(token);
+    let token = queries::get_ephemeral_token_by_room_code(pool, room_code, &token_hash)
+        .await?
+        .ok_or(AuthError::InvalidToken)?;
+
+    if token.expires_at < chrono::Utc::now() {
+        return Err(AuthError::ExpiredToken.into());
+    }
+
+    Ok(token.member_id)
 }
 
+/// Consumes an ephemeral token and returns the ID of the member it was issued to.
 pub async fn validate_websocket_token(
     pool: &sqlx::PgPool,
     token: &str,
     room_code: &str,
-) -> Result<(), AppError> {
-    let token = queries::get_and_delete_ephemeral_token_by_room_code(pool, room_code, token)
+) -> Result<uuid::Uuid, AppError> {
+    let token_hash = cryptography::hash_token(token);
+    let token = queries::get_and_delete_ephemeral_token_by_room_code(pool, room_code, &token_hash)
         .await?
         .ok_or(AuthError::InvalidToken)?;
 
@@ -111,9 +215,96 @@ pub async fn validate_websocket_token(
         return Err(AuthError::ExpiredToken.into());
     }
 
+    Ok(token.member_id)
+}
+
+/// Reports whether the member still holds a usable session.
+pub async fn get_session_state(
+    pool: &sqlx::PgPool,
+    member_id: uuid::Uuid,
+) -> Result<SessionState, AppError> {
+    let state = match queries::get_latest_session_expiry(pool, member_id).await? {
+        None => SessionState::LoggedOut,
+        Some(expires_at) if expires_at < chrono::Utc::now() => SessionState::Expired,
+        Some(_) => SessionState::Active,
+    };
+
+    Ok(state)
+}
+
+/// Sweeps expired tokens and logs how many of each type were removed.
+pub async fn delete_expired_tokens(
+    pool: &sqlx::PgPool,
+    settings: &AuthSettings,
+) -> Result<(), AppError> {
+    let retention_secs = f64::from(settings.expired_session_retention_secs);
+    let deleted = queries::delete_expired_tokens(pool, retention_secs).await?;
+
+    let (mut session, mut ephemeral, mut challenge, mut signature_challenge) = (0, 0, 0, 0);
+    for (token_type, count) in deleted {
+        match token_type {
+            TokenType::Session => session = count,
+            TokenType::Ephemeral => ephemeral = count,
+            TokenType::Challenge => challenge = count,
+            TokenType::SignatureChallenge => signature_challenge = count,
+        }
+    }
+
+    info!(
+        session,
+        ephemeral, challenge, signature_challenge, "Deleted expired tokens"
+    );
     Ok(())
 }
 
+/// Lists the member's active sessions, marking the one identified by `current_session_id`.
+pub async fn list_sessions(
+    pool: &sqlx::PgPool,
+    member_id: uuid::Uuid,
+    current_session_id: uuid::Uuid,
+) -> Result<Vec<SessionResponse>, AppError> {
+    let sessions = queries::get_member_sessions(pool, member_id)
+        .await?
+        .into_iter()
+        .map(|token| SessionResponse {
+            session_id: token.id,
+            current: token.id == current_session_id,
+            created_at: token.created_at,
+            last_seen_at: token.last_seen_at,
+            expires_at: token.expires_at,
+            user_agent: token.user_agent,
+            ip_address: token.ip_address.map(|ip| ip.addr()),
+        })
+        .collect();
+
+    Ok(sessions)
+}
+
+pub async fn revoke_session(
+    pool: &sqlx::PgPool,
+    member_id: uuid::Uuid,
+    session_id: uuid::Uuid,
+) -> Result<(), AppError> {
+    if !queries::delete_session(pool, member_id, session_id).await? {
+        return Err(AuthError::SessionNotFound.into());
+    }
+
+    Ok(())
+}
+
+/// Logs the member out of every session but the current one.
+///
+/// Returns the number of sessions revoked.
+pub async fn revoke_other_sessions(
+    pool: &sqlx::PgPool,
+    member_id: uuid::Uuid,
+    current_session_id: uuid::Uuid,
+) -> Result<u64, AppError> {
+    let revoked = queries::delete_other_sessions(pool, member_id, current_session_id).await?;
+    Ok(revoked)
+}
+
+/// Logs the member out everywhere, deleting all of their tokens.
 pub async fn logout(pool: &sqlx::PgPool, member_id: uuid::Uuid) -> Result<(), AppError> {
     queries::delete_all_tokens(pool, member_id).await?;
     Ok(())
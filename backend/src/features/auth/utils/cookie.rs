@@ -1,12 +1,27 @@
 use crate::config::AuthSettings;
 use axum_extra::extract::cookie::{Cookie, SameSite};
+use time::Duration;
+
+/// Builds the session cookie, set to expire along with the session.
+pub fn new_session_cookie(
+    config: &AuthSettings,
+    value: &str,
+    expires_at: chrono::DateTime<chrono::Utc>,
+) -> Cookie<'static> {
+    let max_age = (expires_at - chrono::Utc::now()).num_seconds().max(0);
 
-pub fn new_session_cookie(config: &AuthSettings, value: &str) -> Cookie<'static> {
     Cookie::build((config.session_cookie_name.clone(), value.to_string()))
         .http_only(true)
         .secure(config.session_cookie_secure)
         .same_site(SameSite::Strict)
-        .permanent()
+        .max_age(Duration::seconds(max_age))
+        .path("/")
+        .build()
+}
+
+/// A cookie matching the session cookie, for removing it from the jar.
+pub fn session_removal_cookie(config: &AuthSettings) -> Cookie<'static> {
+    Cookie::build(config.session_cookie_name.clone())
         .path("/")
         .build()
 }
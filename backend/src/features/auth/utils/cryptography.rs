@@ -1,11 +1,13 @@
-use crate::features::auth::errors::AuthError;
+use crate::config::KeyPolicySettings;
+use crate::features::auth::errors::{AuthError, KeyPolicyViolation};
+use crate::features::auth::models::KeyAlgorithm;
+use crate::features::auth::utils::public_key::PublicKey;
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
 use rand::RngCore;
 use rand::distributions::{Distribution, Uniform};
 use rand::rngs::OsRng;
-use rsa::pkcs8::DecodePublicKey;
-use rsa::{Oaep, RsaPublicKey};
+use rsa::Oaep;
 use sha2::{Digest, Sha256, Sha512};
 use std::fmt::Write;
 use tracing::error;
@@ -20,17 +22,40 @@ pub fn generate_secure_token() -> Result<String, AuthError> {
     Ok(BASE64_STANDARD.encode(token))
 }
 
-pub fn decode_public_key(public_key: &str) -> Result<(Vec<u8>, String), AuthError> {
+/// Digest under which a token is stored, the plaintext only ever goes to the client.
+pub fn hash_token(token: &str) -> String {
+    sha256_hex(token.as_bytes())
+}
+
+/// Decodes a base64 DER public key, returning its bytes, fingerprint and algorithm.
+///
+/// Keys the key policy does not allow are rejected.
+pub fn decode_public_key(
+    public_key: &str,
+    policy: &KeyPolicySettings,
+) -> Result<(Vec<u8>, String, KeyAlgorithm), AuthError> {
+    if public_key.len() > policy.max_encoded_length as usize {
+        return Err(AuthError::KeyPolicyViolation(
+            KeyPolicyViolation::MaxEncodedLength {
+                limit: policy.max_encoded_length,
+                actual: public_key.len(),
+            },
+        ));
+    }
+
     let public_key_bytes = BASE64_STANDARD
         .decode(public_key)
         .or(Err(AuthError::InvalidPublicKey))?;
 
-    // validate the key - we just need the bytes for now.
-    RsaPublicKey::from_public_key_der(&public_key_bytes).or(Err(AuthError::InvalidPublicKey))?;
+    // validate the key - we just need the bytes and the algorithm for now.
+    let key = PublicKey::from_der(&public_key_bytes)?;
+    key.check_policy(policy)
+        .map_err(AuthError::KeyPolicyViolation)?;
+    let algorithm = key.algorithm();
 
     let fingerprint = sha256_hex(&public_key_bytes);
 
-    Ok((public_key_bytes, fingerprint))
+    Ok((public_key_bytes, fingerprint, algorithm))
 }
 
 pub fn sha256_hex(bytes: &[u8]) -> String {
@@ -41,9 +66,12 @@ pub fn sha256_hex(bytes: &[u8]) -> String {
     array_to_hex_string(&fingerprint)
 }
 
+/// Encrypts a challenge with RSA-OAEP/SHA-512, only RSA keys can take encryption challenges.
 pub fn encrypt_challenge_token(token: &str, public_key_bytes: &[u8]) -> Result<String, AuthError> {
-    let public_key =
-        RsaPublicKey::from_public_key_der(public_key_bytes).or(Err(AuthError::InvalidPublicKey))?;
+    let public_key = match PublicKey::from_der(public_key_bytes)? {
+        PublicKey::Rsa(public_key) => public_key,
+        key => return Err(AuthError::UnsupportedChallengeMethod(key.algorithm())),
+    };
 
     let mut rng = OsRng;
 
@@ -55,6 +83,34 @@ pub fn encrypt_challenge_token(token: &str, public_key_bytes: &[u8]) -> Result<S
     Ok(BASE64_STANDARD.encode(encrypted_data))
 }
 
+/// Context the key agreement challenge token is derived under.
+const KEY_AGREEMENT_CONTEXT: &[u8] = b"chimney-login-v1-x25519";
+
+/// Starts a key agreement challenge, only X25519 keys can take these.
+///
+/// Agrees on a secret between a one-time server key and the member's key and returns the token
+/// derived from it along with the one-time public key, base64 encoded. The member repeats the
+/// agreement with their private key and derives the same token, the base64 encoded
+/// SHA-256 of the context, the one-time public key and the shared secret.
+pub fn key_agreement_challenge(public_key_bytes: &[u8]) -> Result<(String, String), AuthError> {
+    let public_key = match PublicKey::from_der(public_key_bytes)? {
+        PublicKey::X25519(public_key) => public_key,
+        key => return Err(AuthError::UnsupportedChallengeMethod(key.algorithm())),
+    };
+
+    let server_secret = x25519_dalek::EphemeralSecret::random_from_rng(OsRng);
+    let server_public = x25519_dalek::PublicKey::from(&server_secret);
+    let shared_secret = server_secret.diffie_hellman(&public_key);
+
+    let mut hasher = Sha256::new();
+    hasher.update(KEY_AGREEMENT_CONTEXT);
+    hasher.update(server_public.as_bytes());
+    hasher.update(shared_secret.as_bytes());
+    let token = BASE64_STANDARD.encode(hasher.finalize());
+
+    Ok((token, BASE64_STANDARD.encode(server_public.as_bytes())))
+}
+
 const ROOM_CODE_LENGTH: usize = 8;
 const ROOM_CODE_CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
 pub fn generate_room_code() -> String {
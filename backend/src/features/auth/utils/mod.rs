@@ -1,4 +1,5 @@
 pub(crate) mod cookie;
 pub mod cryptography;
+pub mod public_key;
 
-pub use cookie::new_session_cookie;
+pub use cookie::{new_session_cookie, session_removal_cookie};
@@ -0,0 +1,266 @@
+use crate::config::KeyPolicySettings;
+use crate::features::auth::errors::{AuthError, KeyPolicyViolation};
+use crate::features::auth::models::KeyAlgorithm;
+use rand::rngs::OsRng;
+use rsa::RsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::pkcs8::spki::{ObjectIdentifier, SubjectPublicKeyInfoRef};
+use rsa::pss;
+use rsa::signature::Verifier;
+use rsa::traits::PublicKeyParts;
+use sha2::Sha256;
+
+const RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+const X25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.110");
+const ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+const X25519_KEY_LENGTH: usize = 32;
+
+/// A member's public key, parsed from its DER encoded `SubjectPublicKeyInfo`.
+pub enum PublicKey {
+    Rsa(RsaPublicKey),
+    EcdsaP256(p256::ecdsa::VerifyingKey),
+    /// Only usable for key agreement, so it cannot sign challenges and signs in with a key
+    /// agreement challenge instead.
+    X25519(x25519_dalek::PublicKey),
+    Ed25519(ed25519_dalek::VerifyingKey),
+}
+
+impl PublicKey {
+    /// Parses the key, picking the algorithm from the key's algorithm identifier.
+    pub fn from_der(der: &[u8]) -> Result<Self, AuthError> {
+        let info = SubjectPublicKeyInfoRef::try_from(der).or(Err(AuthError::InvalidPublicKey))?;
+
+        let key = match info.algorithm.oid {
+            RSA_ENCRYPTION => RsaPublicKey::from_public_key_der(der).map(Self::Rsa).ok(),
+            EC_PUBLIC_KEY => p256::ecdsa::VerifyingKey::from_public_key_der(der)
+                .map(Self::EcdsaP256)
+                .ok(),
+            X25519 => info
+                .subject_public_key
+                .as_bytes()
+                .and_then(|bytes| <[u8; X25519_KEY_LENGTH]>::try_from(bytes).ok())
+                .map(x25519_dalek::PublicKey::from)
+                // a key of small order agrees on a secret known to anyone, which would give
+                // away its key agreement challenges
+                .filter(|key| {
+                    x25519_dalek::EphemeralSecret::random_from_rng(OsRng)
+                        .diffie_hellman(key)
+                        .was_contributory()
+                })
+                .map(Self::X25519),
+            ED25519 => ed25519_dalek::VerifyingKey::from_public_key_der(der)
+                .map(Self::Ed25519)
+                .ok(),
+            _ => None,
+        };
+
+        key.ok_or(AuthError::InvalidPublicKey)
+    }
+
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        match self {
+            Self::Rsa(_) => KeyAlgorithm::Rsa,
+            Self::EcdsaP256(_) => KeyAlgorithm::P256,
+            Self::X25519(_) => KeyAlgorithm::X25519,
+            Self::Ed25519(_) => KeyAlgorithm::Ed25519,
+        }
+    }
+
+    /// Checks the key against the key policy.
+    pub fn check_policy(&self, policy: &KeyPolicySettings) -> Result<(), KeyPolicyViolation> {
+        let algorithm = self.algorithm();
+        if !policy.allowed_algorithms.contains(&algorithm) {
+            return Err(KeyPolicyViolation::AllowedAlgorithms {
+                allowed: policy.allowed_algorithms.clone(),
+                actual: algorithm,
+            });
+        }
+
+        if let Self::Rsa(key) = self {
+            let modulus_bits = key.n().bits();
+            if modulus_bits < policy.min_rsa_modulus_bits as usize {
+                return Err(KeyPolicyViolation::MinRsaModulusBits {
+                    minimum: policy.min_rsa_modulus_bits,
+                    actual: modulus_bits,
+                });
+            }
+
+            let exponent = key.e();
+            let allowed = policy
+                .allowed_rsa_exponents
+                .iter()
+                .any(|allowed| *exponent == rsa::BigUint::from(*allowed));
+            if !allowed {
+                return Err(KeyPolicyViolation::AllowedRsaExponents {
+                    allowed: policy.allowed_rsa_exponents.clone(),
+                    actual: exponent.to_str_radix(16),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the key can sign, X25519 keys only do key agreement.
+    pub fn can_sign(&self) -> bool {
+        !matches!(self, Self::X25519(_))
+    }
+
+    /// Verifies a signature over `message`.
+    ///
+    /// RSA keys sign with RSA-PSS over SHA-256 with a 32 byte salt, P-256 keys with ECDSA over
+    /// SHA-256, as either a raw `r || s` pair or DER, and Ed25519 keys with plain Ed25519.
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), AuthError> {
+        let verified = match self {
+            Self::Rsa(key) => pss::Signature::try_from(signature).is_ok_and(|signature| {
+                pss::VerifyingKey::<Sha256>::new(key.clone())
+                    .verify(message, &signature)
+                    .is_ok()
+            }),
+            Self::EcdsaP256(key) => p256::ecdsa::Signature::from_slice(signature)
+                .or_else(|_| p256::ecdsa::Signature::from_der(signature))
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+            Self::Ed25519(key) => ed25519_dalek::Signature::from_slice(signature)
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+            Self::X25519(_) => {
+                return Err(AuthError::UnsupportedChallengeMethod(KeyAlgorithm::X25519));
+            }
+        };
+
+        if verified {
+            Ok(())
+        } else {
+            Err(AuthError::InvalidSignature)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use base64::prelude::BASE64_STANDARD;
+    use rand::rngs::OsRng;
+    use rsa::pkcs8::EncodePublicKey;
+    use rsa::signature::{RandomizedSigner, SignatureEncoding, Signer};
+
+    const MESSAGE: &[u8] = b"challenge";
+
+    #[test]
+    fn test_verify_rsa_pss() {
+        let private_key = rsa::RsaPrivateKey::new(&mut OsRng, 1024).unwrap();
+        let der = private_key.to_public_key().to_public_key_der().unwrap();
+        let signature = pss::SigningKey::<Sha256>::new(private_key)
+            .sign_with_rng(&mut OsRng, MESSAGE)
+            .to_vec();
+
+        let key = PublicKey::from_der(der.as_bytes()).unwrap();
+        assert!(key.verify(MESSAGE, &signature).is_ok());
+        assert!(key.verify(b"other", &signature).is_err());
+    }
+
+    #[test]
+    fn test_verify_ecdsa_p256() {
+        let signing_key = p256::ecdsa::SigningKey::random(&mut OsRng);
+        let der = signing_key.verifying_key().to_public_key_der().unwrap();
+        let signature: p256::ecdsa::Signature = signing_key.sign(MESSAGE);
+
+        let key = PublicKey::from_der(der.as_bytes()).unwrap();
+        assert!(key.verify(MESSAGE, &signature.to_bytes()).is_ok());
+        assert!(key.verify(MESSAGE, signature.to_der().as_bytes()).is_ok());
+        assert!(key.verify(b"other", &signature.to_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_verify_ed25519() {
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let der = signing_key.verifying_key().to_public_key_der().unwrap();
+        let signature = signing_key.sign(MESSAGE);
+
+        let key = PublicKey::from_der(der.as_bytes()).unwrap();
+        assert!(key.verify(MESSAGE, &signature.to_bytes()).is_ok());
+        assert!(key.verify(b"other", &signature.to_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_parse_x25519() {
+        // RFC 8410 example key
+        let der = BASE64_STANDARD
+            .decode("MCowBQYDK2VuAyEAGb9ECWmEzf6FQbrBZ9w7lshQhqowtrbLDFw4rXAxZuE=")
+            .unwrap();
+
+        let key = PublicKey::from_der(&der).unwrap();
+        assert_eq!(key.algorithm(), KeyAlgorithm::X25519);
+        assert!(key.verify(MESSAGE, &[0; 64]).is_err());
+    }
+
+    #[test]
+    fn test_rejects_small_order_x25519() {
+        // the RFC 8410 example key's header followed by the identity point
+        let mut der = BASE64_STANDARD.decode("MCowBQYDK2VuAyEA").unwrap();
+        der.extend([0; 32]);
+
+        assert!(PublicKey::from_der(&der).is_err());
+    }
+
+    fn policy() -> KeyPolicySettings {
+        KeyPolicySettings {
+            allowed_algorithms: vec![KeyAlgorithm::Rsa, KeyAlgorithm::P256],
+            min_rsa_modulus_bits: 2048,
+            allowed_rsa_exponents: vec![65537],
+            max_encoded_length: 2048,
+        }
+    }
+
+    #[test]
+    fn test_policy_rejects_small_rsa_moduli() {
+        let private_key = rsa::RsaPrivateKey::new(&mut OsRng, 1024).unwrap();
+        let key = PublicKey::Rsa(private_key.to_public_key());
+
+        assert!(matches!(
+            key.check_policy(&policy()),
+            Err(KeyPolicyViolation::MinRsaModulusBits { actual: 1024, .. })
+        ));
+    }
+
+    #[test]
+    fn test_policy_rejects_disallowed_exponents() {
+        let private_key = rsa::RsaPrivateKey::new(&mut OsRng, 1024).unwrap();
+        let key = PublicKey::Rsa(private_key.to_public_key());
+        let policy = KeyPolicySettings {
+            min_rsa_modulus_bits: 1024,
+            allowed_rsa_exponents: vec![3],
+            ..policy()
+        };
+
+        assert!(matches!(
+            key.check_policy(&policy),
+            Err(KeyPolicyViolation::AllowedRsaExponents { .. })
+        ));
+    }
+
+    #[test]
+    fn test_policy_checks_algorithms() {
+        let p256 = p256::ecdsa::SigningKey::random(&mut OsRng);
+        let ed25519 = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+
+        assert!(
+            PublicKey::EcdsaP256(*p256.verifying_key())
+                .check_policy(&policy())
+                .is_ok()
+        );
+        assert!(matches!(
+            PublicKey::Ed25519(ed25519.verifying_key()).check_policy(&policy()),
+            Err(KeyPolicyViolation::AllowedAlgorithms {
+                actual: KeyAlgorithm::Ed25519,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_garbage_keys() {
+        assert!(PublicKey::from_der(b"not a key").is_err());
+    }
+}
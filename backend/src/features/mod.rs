@@ -1,10 +1,24 @@
+use crate::config::Settings;
+use crate::jobs::Job;
 use crate::state::SharedState;
 use axum::Router;
 
 // pub mod auth;
-mod auth;
+pub(crate) mod auth;
 mod health;
-mod room;
+pub(crate) mod room;
+
+/// Spawns the long-running tasks the features rely on.
+pub fn spawn_background_tasks(state: &SharedState) {
+    room::spawn_background_tasks(state);
+}
+
+/// Recurring jobs the features rely on, see [`crate::jobs`].
+pub fn jobs(config: &Settings) -> Vec<Job> {
+    let mut jobs = auth::jobs(&config.auth);
+    jobs.extend(room::jobs(&config.room));
+    jobs
+}
 
 pub fn build_router() -> Router<SharedState> {
     Router::new().nest("/v1", build_v1_router())
@@ -15,4 +29,5 @@ fn build_v1_router() -> Router<SharedState> {
         .nest("/health", health::build_router())
         .nest("/room", room::build_router())
         .nest("/auth", auth::build_router())
+        .layer(axum::middleware::from_fn(auth::refresh_session_cookie))
 }
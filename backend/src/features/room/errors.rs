@@ -1,16 +1,29 @@
 use crate::error::AppError;
-use crate::features::room::models::GamePhase;
+use crate::features::auth::KeySuite;
+use crate::features::room::models::{GamePhase, MemberRole};
 use axum::http::StatusCode;
 
 pub enum RoomError {
     RoomNotFound,
+    MemberNotFound,
+    AlreadyMember,
+    NameTaken,
+    OwnerCannotLeave,
+    CannotTargetSelf,
+    NothingToEvict,
+    NotEnoughMembers,
     RoomFull,
-    RequiresOwnerPermission,
+    IncompatibleKey(ExpectedCurrent<KeySuite>),
+    InsufficientRole(ExpectedCurrent<MemberRole>),
     InvalidGamePhase(ExpectedCurrent<GamePhase>),
     AlreadySentMessage,
     InvalidSeed,
     LiarLiarPantsOnFire(String),
     InvalidRejectionProof,
+    UnsupportedProtocolVersion(UnsupportedVersion),
+    UnsupportedAssignmentProtocol(UnsupportedVersion),
+    InvalidSocketMessage(String),
+    TooManyConnections(ConnectionLimit),
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -19,7 +32,21 @@ pub struct ExpectedCurrent<T> {
     pub(crate) current: T,
 }
 
+#[derive(Debug, serde::Serialize)]
+pub struct UnsupportedVersion {
+    pub(crate) requested: u16,
+    pub(crate) supported: &'static [u16],
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "scope", rename_all = "lowercase")]
+pub enum ConnectionLimit {
+    Member { limit: u32 },
+    Room { limit: u32 },
+}
+
 impl From<RoomError> for AppError {
+    #[allow(clippy::too_many_lines)]
     fn from(err: RoomError) -> Self {
         match err {
             RoomError::RoomNotFound => AppError::new(
@@ -27,16 +54,58 @@ impl From<RoomError> for AppError {
                 "The specified room does not exist.",
                 StatusCode::NOT_FOUND,
             ),
+            RoomError::MemberNotFound => AppError::new(
+                "MEMBER_NOT_FOUND",
+                "The specified member is not in your room.",
+                StatusCode::NOT_FOUND,
+            ),
+            RoomError::AlreadyMember => AppError::new(
+                "ALREADY_MEMBER",
+                "This key is already a member of the room.",
+                StatusCode::CONFLICT,
+            ),
+            RoomError::NameTaken => AppError::new(
+                "NAME_TAKEN",
+                "Another member of the room already uses this name. Please pick another.",
+                StatusCode::CONFLICT,
+            ),
+            RoomError::OwnerCannotLeave => AppError::new(
+                "OWNER_CANNOT_LEAVE",
+                "The room owner cannot leave or be removed from the room. Transfer ownership first.",
+                StatusCode::BAD_REQUEST,
+            ),
+            RoomError::CannotTargetSelf => AppError::new(
+                "CANNOT_TARGET_SELF",
+                "This action cannot be performed on yourself.",
+                StatusCode::BAD_REQUEST,
+            ),
+            RoomError::NothingToEvict => AppError::new(
+                "NOTHING_TO_EVICT",
+                "Members can only be evicted while a game is in progress.",
+                StatusCode::BAD_REQUEST,
+            ),
+            RoomError::NotEnoughMembers => AppError::new(
+                "NOT_ENOUGH_MEMBERS",
+                "Too few members would be left in the room to play.",
+                StatusCode::BAD_REQUEST,
+            ),
             RoomError::RoomFull => AppError::new(
                 "ROOM_FULL",
                 "The room is full. Please try another room.",
                 StatusCode::FORBIDDEN,
             ),
-            RoomError::RequiresOwnerPermission => AppError::new(
-                "REQUIRES_OWNER_PERMISSION",
-                "This action requires owner permissions.",
+            RoomError::IncompatibleKey(expected_current) => AppError::new(
+                "INCOMPATIBLE_KEY_ALGORITHM",
+                "Your key cannot be used with the keys of the other members of this room.",
+                StatusCode::BAD_REQUEST,
+            )
+            .with_details(expected_current),
+            RoomError::InsufficientRole(expected_current) => AppError::new(
+                "INSUFFICIENT_ROLE",
+                "Your role in the room does not allow this action.",
                 StatusCode::FORBIDDEN,
-            ),
+            )
+            .with_details(expected_current),
             RoomError::InvalidGamePhase(expected_current) => AppError::new(
                 "INVALID_GAME_PHASE",
                 "The game is not in the correct phase for this action.",
@@ -64,6 +133,30 @@ impl From<RoomError> for AppError {
                 "The provided rejection proof is invalid.",
                 StatusCode::BAD_REQUEST,
             ),
+            RoomError::UnsupportedProtocolVersion(versions) => AppError::new(
+                "UNSUPPORTED_PROTOCOL_VERSION",
+                "The requested websocket protocol version is not supported.",
+                StatusCode::BAD_REQUEST,
+            )
+            .with_details(versions),
+            RoomError::UnsupportedAssignmentProtocol(versions) => AppError::new(
+                "UNSUPPORTED_ASSIGNMENT_PROTOCOL",
+                "New rooms cannot be created with the requested assignment protocol version.",
+                StatusCode::BAD_REQUEST,
+            )
+            .with_details(versions),
+            RoomError::InvalidSocketMessage(reason) => AppError::new(
+                "INVALID_SOCKET_MESSAGE",
+                "The message could not be understood.",
+                StatusCode::BAD_REQUEST,
+            )
+            .with_details(reason),
+            RoomError::TooManyConnections(limit) => AppError::new(
+                "TOO_MANY_CONNECTIONS",
+                "Too many open connections. Close another connection and try again.",
+                StatusCode::TOO_MANY_REQUESTS,
+            )
+            .with_details(limit),
         }
     }
 }
@@ -1,15 +1,59 @@
+use super::models::MemberRole;
 use super::{schemas, service};
 use crate::error::AppError;
 use crate::features::auth;
 use crate::state::SharedState;
 use axum::Json;
 use axum::extract::{ConnectInfo, State};
-use axum::http::{HeaderMap, StatusCode};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
 use axum::response::IntoResponse;
 use axum_extra::extract::CookieJar;
 use std::net::SocketAddr;
 use validator::Validate;
 
+pub async fn get_room(
+    State(state): State<SharedState>,
+    auth::Session(session): auth::Session,
+) -> Result<impl IntoResponse, AppError> {
+    let room = service::get_room(&state.db, &session.member_id).await?;
+
+    Ok(Json(room))
+}
+
+pub async fn get_roster(
+    State(state): State<SharedState>,
+    auth::Session(session): auth::Session,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let roster = service::get_roster(&state.db, &session.member_id).await?;
+
+    let etag = format!("\"{}\"", roster.version);
+    let etag_header = HeaderValue::from_str(&etag).map_err(|_| AppError::unknown_error())?;
+    let cache_headers = [
+        (header::ETAG, etag_header),
+        (
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("private, no-cache"),
+        ),
+    ];
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+        });
+
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+
+    Ok((cache_headers, Json(roster)).into_response())
+}
+
 pub async fn create_room(
     State(state): State<SharedState>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
@@ -26,19 +70,34 @@ pub async fn create_room(
         &body.public_key,
         &body.seed_hash,
         body.max_players,
+        &service::phase_deadlines(&state.config.room, &body),
+        body.protocol_version,
+        &state.config.auth.key_policy,
     )
     .await?;
 
     let ip_address = Some(addr.ip());
     let user_agent = headers.get("User-Agent").and_then(|h| h.to_str().ok());
 
-    let session_token =
-        auth::service::create_session_token(&state.db, user_id, user_agent, ip_address).await?;
+    let (session_token, expires_at) = auth::service::create_session_token(
+        &state.db,
+        &state.config.auth,
+        user_id,
+        user_agent,
+        ip_address,
+    )
+    .await?;
     let session_cookie =
-        auth::utils::cookie::new_session_cookie(&state.config.auth, &session_token);
+        auth::utils::new_session_cookie(&state.config.auth, &session_token, expires_at);
 
-    let ephemeral_token =
-        auth::service::create_ephemeral_token(&state.db, user_id, user_agent, ip_address).await?;
+    let ephemeral_token = auth::service::create_ephemeral_token(
+        &state.db,
+        &state.config.auth,
+        user_id,
+        user_agent,
+        ip_address,
+    )
+    .await?;
 
     Ok((
         StatusCode::CREATED,
@@ -61,23 +120,37 @@ pub async fn join_room(
 
     let user_id = service::join_room(
         &state.db,
+        &state.room_hub,
         &body.room_id,
         &body.name,
         &body.public_key,
         &body.seed_hash,
+        &state.config.auth.key_policy,
     )
     .await?;
 
     let ip_address = Some(addr.ip());
     let user_agent = headers.get("User-Agent").and_then(|h| h.to_str().ok());
 
-    let session_token =
-        auth::service::create_session_token(&state.db, user_id, user_agent, ip_address).await?;
+    let (session_token, expires_at) = auth::service::create_session_token(
+        &state.db,
+        &state.config.auth,
+        user_id,
+        user_agent,
+        ip_address,
+    )
+    .await?;
     let session_cookie =
-        auth::utils::cookie::new_session_cookie(&state.config.auth, &session_token);
+        auth::utils::new_session_cookie(&state.config.auth, &session_token, expires_at);
 
-    let ephemeral_token =
-        auth::service::create_ephemeral_token(&state.db, user_id, user_agent, ip_address).await?;
+    let ephemeral_token = auth::service::create_ephemeral_token(
+        &state.db,
+        &state.config.auth,
+        user_id,
+        user_agent,
+        ip_address,
+    )
+    .await?;
 
     Ok((
         StatusCode::CREATED,
@@ -86,15 +159,105 @@ pub async fn join_room(
     ))
 }
 
+pub async fn leave_room(
+    State(state): State<SharedState>,
+    auth::Session(session): auth::Session,
+    cookies: CookieJar,
+) -> Result<impl IntoResponse, AppError> {
+    service::leave_room(&state.db, &state.room_hub, &session.member_id).await?;
+
+    // the session was deleted along with the member
+    let removal_cookie = auth::utils::session_removal_cookie(&state.config.auth);
+
+    Ok((StatusCode::NO_CONTENT, cookies.remove(removal_cookie)))
+}
+
+pub async fn kick_member(
+    State(state): State<SharedState>,
+    auth::Session(session): auth::Session,
+    Json(body): Json<schemas::TargetMemberRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    service::kick_member(
+        &state.db,
+        &state.room_hub,
+        &session.member_id,
+        &body.member_id,
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn evict_member(
+    State(state): State<SharedState>,
+    auth::Session(session): auth::Session,
+    Json(body): Json<schemas::TargetMemberRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    service::requires_role(&state.db, &session.member_id, MemberRole::Owner).await?;
+
+    service::evict_member(
+        &state.db,
+        &state.room_hub,
+        &session.member_id,
+        &body.member_id,
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn transfer_ownership(
+    State(state): State<SharedState>,
+    auth::Session(session): auth::Session,
+    Json(body): Json<schemas::TransferOwnershipRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    service::requires_role(&state.db, &session.member_id, MemberRole::Owner).await?;
+
+    service::transfer_ownership(
+        &state.db,
+        &state.room_hub,
+        &session.member_id,
+        &body.member_id,
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn set_member_role(
+    State(state): State<SharedState>,
+    auth::Session(session): auth::Session,
+    Json(body): Json<schemas::SetRoleRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    service::requires_role(&state.db, &session.member_id, MemberRole::Owner).await?;
+
+    service::set_member_role(
+        &state.db,
+        &state.room_hub,
+        &session.member_id,
+        &body.member_id,
+        body.role.into(),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn start_game(
     State(state): State<SharedState>,
     auth::Session(session): auth::Session,
     Json(body): Json<schemas::OnionMessageRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    service::requires_owner_permission(&state.db, &session.member_id).await?;
+    service::requires_role(&state.db, &session.member_id, MemberRole::Organiser).await?;
 
-    service::start_game(&state.db, &session.member_id).await?;
-    service::handle_onion_message(&state.db, &session.member_id, &body.message_content).await?;
+    service::start_game(&state.db, &state.room_hub, &session.member_id).await?;
+    service::handle_onion_message(
+        &state.db,
+        &state.room_hub,
+        &session.member_id,
+        &body.message_content,
+    )
+    .await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -104,7 +267,13 @@ pub async fn handle_onion_message(
     auth::Session(session): auth::Session,
     Json(body): Json<schemas::OnionMessageRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    service::handle_onion_message(&state.db, &session.member_id, &body.message_content).await?;
+    service::handle_onion_message(
+        &state.db,
+        &state.room_hub,
+        &session.member_id,
+        &body.message_content,
+    )
+    .await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -114,7 +283,7 @@ pub async fn handle_seed_reveal(
     auth::Session(session): auth::Session,
     Json(body): Json<schemas::SeedRevealRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    service::reveal_seed(&state.db, &session.member_id, &body.seed).await?;
+    service::reveal_seed(&state.db, &state.room_hub, &session.member_id, &body.seed).await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -124,7 +293,7 @@ pub async fn handle_verification(
     auth::Session(session): auth::Session,
     Json(body): Json<schemas::VerificationRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    service::handle_verification(&state.db, &session.member_id, &body).await?;
+    service::handle_verification(&state.db, &state.room_hub, &session.member_id, &body).await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -138,3 +307,16 @@ pub async fn handle_seed_commit(
 
     Ok(StatusCode::NO_CONTENT)
 }
+
+pub async fn get_protocol_versions() -> impl IntoResponse {
+    Json(service::get_protocol_versions())
+}
+
+pub async fn get_presence(
+    State(state): State<SharedState>,
+    auth::Session(session): auth::Session,
+) -> Result<impl IntoResponse, AppError> {
+    let members = service::get_room_presence(&state.db, &session.member_id).await?;
+
+    Ok(Json(schemas::RoomPresenceResponse { members }))
+}
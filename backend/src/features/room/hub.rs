@@ -0,0 +1,195 @@
+use super::models::{GamePhase, MemberRole, StallPolicy};
+use super::queries;
+use crate::state::SharedState;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Number of events a slow socket may fall behind before it starts missing them.
+const ROOM_CHANNEL_CAPACITY: usize = 64;
+
+/// Postgres notification channel shared by every backend instance.
+pub const ROOM_EVENTS_CHANNEL: &str = "room_events";
+
+const LISTENER_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RoomEvent {
+    MemberJoined {
+        member_id: Uuid,
+        name: String,
+    },
+    MemberLeft {
+        member_id: Uuid,
+    },
+    /// An organiser or the owner removed the member from the room.
+    MemberKicked {
+        member_id: Uuid,
+    },
+    RoleChanged {
+        member_id: Uuid,
+        role: MemberRole,
+    },
+    GameStarted,
+    OnionRoundOpened {
+        round: i32,
+    },
+    PhaseChanged {
+        phase: GamePhase,
+    },
+    /// The previous iteration was aborted and a new one started. Members commit a new seed with
+    /// `/room/commit/seed` to take part.
+    IterationRestarted {
+        iteration: i32,
+    },
+    /// The phase ran past its deadline without every member submitting.
+    PhaseExpired {
+        phase: GamePhase,
+        non_responders: Vec<Uuid>,
+        stall_policy: StallPolicy,
+    },
+    MemberOnline {
+        member_id: Uuid,
+    },
+    MemberOffline {
+        member_id: Uuid,
+    },
+}
+
+impl RoomEvent {
+    /// Transient events describe the moment rather than the room and are not kept in the
+    /// room's event log.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            RoomEvent::MemberOnline { .. } | RoomEvent::MemberOffline { .. }
+        )
+    }
+}
+
+/// Payload of a notification on [`ROOM_EVENTS_CHANNEL`].
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RoomNotification {
+    pub room_id: Uuid,
+    /// Position in the room's event log, absent for transient events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<i64>,
+    pub event: RoomEvent,
+}
+
+/// Fan-out of room events to every socket connected to that room.
+///
+/// Events are published through Postgres so that sockets held by other instances receive them
+/// too. Each instance runs [`listen`] to forward notifications to its local subscribers.
+#[derive(Default)]
+pub struct RoomHub {
+    rooms: Mutex<HashMap<Uuid, broadcast::Sender<Arc<RoomNotification>>>>,
+}
+
+impl RoomHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&self, room_id: Uuid) -> broadcast::Receiver<Arc<RoomNotification>> {
+        let mut rooms = self.rooms.lock().expect("room hub lock poisoned");
+        rooms
+            .entry(room_id)
+            .or_insert_with(|| broadcast::channel(ROOM_CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    /// Drops the room's channel once no local socket is subscribed to it any more.
+    ///
+    /// Called whenever a socket goes away, so rooms that fall quiet do not keep their channel
+    /// until the next event happens to find it unused.
+    pub fn prune(&self, room_id: Uuid) {
+        let mut rooms = self.rooms.lock().expect("room hub lock poisoned");
+        if rooms
+            .get(&room_id)
+            .is_some_and(|sender| sender.receiver_count() == 0)
+        {
+            rooms.remove(&room_id);
+        }
+    }
+
+    /// Appends an event to the room's event log and publishes it to every instance.
+    ///
+    /// The state change the event describes has already been committed, so a failure here is
+    /// logged rather than returned to the caller.
+    pub async fn publish(&self, db: &sqlx::PgPool, room_id: Uuid, event: RoomEvent) {
+        let result = if event.is_transient() {
+            let notification = RoomNotification {
+                room_id,
+                seq: None,
+                event,
+            };
+            queries::notify_room_event(db, &notification).await
+        } else {
+            queries::record_room_event(db, &room_id, &event).await
+        };
+
+        if let Err(err) = result {
+            error!(err=?err, %room_id, "Failed to publish room event");
+        }
+    }
+
+    /// Delivers an event to the sockets connected to this instance.
+    fn dispatch(&self, notification: RoomNotification) {
+        let room_id = notification.room_id;
+        let mut rooms = self.rooms.lock().expect("room hub lock poisoned");
+        let Some(sender) = rooms.get(&room_id) else {
+            // nobody is listening to this room
+            return;
+        };
+
+        if sender.send(Arc::new(notification)).is_err() {
+            // every receiver has been dropped, so the channel is no longer needed
+            rooms.remove(&room_id);
+        }
+    }
+}
+
+/// Forwards room event notifications to local sockets for as long as the process runs.
+pub async fn listen(state: SharedState) {
+    loop {
+        let mut listener = match connect_listener(&state.db).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!(err=?err, "Failed to listen for room events, retrying");
+                tokio::time::sleep(LISTENER_RETRY_DELAY).await;
+                continue;
+            }
+        };
+        info!("Listening for room events");
+
+        loop {
+            // the listener reconnects by itself, notifications sent while it is away are lost
+            let notification = match listener.recv().await {
+                Ok(notification) => notification,
+                Err(err) => {
+                    warn!(err=?err, "Room event listener lost its connection");
+                    tokio::time::sleep(LISTENER_RETRY_DELAY).await;
+                    continue;
+                }
+            };
+
+            match serde_json::from_str::<RoomNotification>(notification.payload()) {
+                Ok(notification) => state.room_hub.dispatch(notification),
+                Err(err) => error!(err=?err, "Received a malformed room event"),
+            }
+        }
+    }
+}
+
+async fn connect_listener(db: &sqlx::PgPool) -> Result<PgListener, sqlx::Error> {
+    let mut listener = PgListener::connect_with(db).await?;
+    listener.listen(ROOM_EVENTS_CHANNEL).await?;
+    Ok(listener)
+}
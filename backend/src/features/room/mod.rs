@@ -1,21 +1,35 @@
 mod errors;
 mod handlers;
+pub(crate) mod hub;
 mod models;
+mod protocol;
 mod queries;
 mod schemas;
 mod service;
 mod utils;
 mod websocket;
 
+use crate::config::RoomSettings;
+use crate::jobs::Job;
 use crate::state::SharedState;
-use axum::routing::{any, post};
+use axum::routing::{any, get, post};
 use serde::Deserialize;
+use std::time::Duration;
 
 pub fn build_router() -> axum::Router<SharedState> {
     axum::Router::new()
+        .route("/", get(handlers::get_room))
         .route("/ws", any(websocket::upgrade_handler))
+        .route("/presence", get(handlers::get_presence))
+        .route("/protocol-versions", get(handlers::get_protocol_versions))
+        .route("/roster", get(handlers::get_roster))
         .route("/create", post(handlers::create_room))
         .route("/join", post(handlers::join_room))
+        .route("/leave", post(handlers::leave_room))
+        .route("/kick", post(handlers::kick_member))
+        .route("/evict", post(handlers::evict_member))
+        .route("/role", post(handlers::set_member_role))
+        .route("/transfer-ownership", post(handlers::transfer_ownership))
         .route("/start", post(handlers::start_game))
         .route("/publish/message", post(handlers::handle_onion_message))
         .route("/publish/seed", post(handlers::handle_seed_reveal))
@@ -23,8 +37,67 @@ pub fn build_router() -> axum::Router<SharedState> {
         .route("/commit/seed", post(handlers::handle_seed_commit)) // commit seed for next iteration
 }
 
+/// Spawns the room tasks that run for as long as the process does.
+pub(crate) fn spawn_background_tasks(state: &SharedState) {
+    tokio::spawn(hub::listen(state.clone()));
+}
+
+/// Recurring room chores, run by the job runner.
+pub(crate) fn jobs(settings: &RoomSettings) -> Vec<Job> {
+    let secs = |secs: u32| Duration::from_secs(secs.into());
+
+    let mut jobs = vec![
+        Job::new(
+            "room.expire_phases",
+            secs(settings.deadline_check_interval_secs),
+            |state| {
+                Box::pin(
+                    async move { service::handle_expired_phases(&state.db, &state.room_hub).await },
+                )
+            },
+        ),
+        Job::new(
+            "room.promote_inactive_owners",
+            secs(settings.owner_check_interval_secs),
+            |state| {
+                Box::pin(async move {
+                    service::promote_inactive_owners(&state.db, &state.room_hub, &state.config.room)
+                        .await
+                })
+            },
+        ),
+        Job::new(
+            "room.reap_expired_connections",
+            secs(settings.connection_reap_interval_secs),
+            |state| {
+                Box::pin(async move {
+                    service::reap_expired_connections(&state.db, &state.room_hub).await
+                })
+            },
+        ),
+    ];
+
+    if settings.abandoned_room_ttl_secs.is_some() {
+        jobs.push(Job::new(
+            "room.delete_abandoned_rooms",
+            secs(settings.abandoned_room_check_interval_secs),
+            |state| {
+                Box::pin(async move {
+                    service::delete_abandoned_rooms(&state.db, &state.config.room).await
+                })
+            },
+        ));
+    }
+
+    jobs
+}
+
 #[derive(Deserialize)]
 struct WebsocketOptions {
     room: String,
     token: String,
+    /// Protocol version the client wants to speak, defaults to the latest supported version.
+    protocol: Option<u16>,
+    /// Sequence number of the last room event the client saw, events after it are replayed.
+    last_seq: Option<i64>,
 }
@@ -1,10 +1,13 @@
+use crate::features::auth::{KeyAlgorithm, KeySuite};
+
 pub struct Room {
     pub id: uuid::Uuid,
+    pub key_suite: KeySuite,
     pub max_members: Option<i32>,
     pub member_count: Option<i64>,
 }
 
-#[derive(sqlx::Type, serde::Serialize, Eq, PartialEq, Debug)]
+#[derive(sqlx::Type, serde::Serialize, serde::Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
 #[sqlx(type_name = "game_phase", rename_all = "snake_case")]
 pub enum GamePhase {
     Lobby,
@@ -13,6 +16,69 @@ pub enum GamePhase {
     Verification,
     Rejected,
     Completed,
+    /// The iteration was given up on. Either a phase ran past its deadline and the room's stall
+    /// policy aborted the game, or members were evicted, by the owner or by the evict-and-restart
+    /// policy, and a new iteration replaced this one.
+    Aborted,
+}
+
+/// What happens when a game phase runs past its deadline.
+#[derive(sqlx::Type, serde::Serialize, serde::Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+#[sqlx(type_name = "stall_policy", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum StallPolicy {
+    /// Mark the iteration as stalled and leave it to the members.
+    Flag,
+    Abort,
+    /// Evict the members who did not respond and restart with a new iteration.
+    EvictAndRestart,
+}
+
+/// Per-room phase timeouts, `None` means the phase has no deadline.
+#[derive(Debug)]
+pub struct PhaseDeadlines {
+    pub santa_id_timeout_secs: Option<i32>,
+    pub seed_reveal_timeout_secs: Option<i32>,
+    pub verification_timeout_secs: Option<i32>,
+    pub stall_policy: StallPolicy,
+}
+
+/// The iteration that replaced an aborted one.
+#[derive(Debug)]
+pub struct IterationRestart {
+    pub iteration: i32,
+    /// Set when the owner was evicted and ownership passed to another member.
+    pub new_owner_id: Option<uuid::Uuid>,
+}
+
+/// A game iteration whose phase ran past its deadline.
+#[derive(Debug)]
+pub struct ExpiredPhase {
+    pub room_id: uuid::Uuid,
+    pub iteration_id: uuid::Uuid,
+    pub phase: GamePhase,
+    pub stall_policy: StallPolicy,
+}
+
+/// What a member may do in their room, ordered from least to most privileged.
+#[derive(
+    sqlx::Type,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Debug,
+)]
+#[sqlx(type_name = "member_role", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum MemberRole {
+    Participant,
+    Organiser,
+    Owner,
 }
 
 #[derive(Debug)]
@@ -23,3 +89,75 @@ pub struct OnionRoundStatus {
     pub total_users: i64,
     pub users_remaining: i64,
 }
+
+#[derive(Debug, serde::Serialize)]
+pub struct MemberPresence {
+    pub member_id: uuid::Uuid,
+    pub name: String,
+    pub online: bool,
+    /// Most recent activity on any of the member's tokens or sockets.
+    pub last_active_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Outcome of opening a live connection under the configured connection limits.
+#[derive(Debug)]
+pub enum ConnectionSlot {
+    Opened {
+        connection_id: uuid::Uuid,
+        /// Live connections the member holds, including the new one.
+        open_connections: i64,
+    },
+    MemberLimitReached,
+    RoomLimitReached,
+}
+
+/// The member's room and where its current game iteration stands, as seen by that member.
+#[derive(Debug)]
+pub struct RoomSnapshot {
+    pub room_id: uuid::Uuid,
+    pub name: String,
+    pub join_code: String,
+    pub max_members: Option<i32>,
+    pub protocol_version: i16,
+    pub role: MemberRole,
+    pub iteration: i32,
+    pub phase: GamePhase,
+    pub phase_deadline: Option<chrono::DateTime<chrono::Utc>>,
+    pub stalled_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub has_revealed_seed: bool,
+    pub has_verified: bool,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct RoomMember {
+    pub member_id: uuid::Uuid,
+    pub name: String,
+    pub role: MemberRole,
+}
+
+/// An owner who has been inactive for too long and the member taking over from them.
+#[derive(Debug)]
+#[allow(clippy::struct_field_names)]
+pub struct OwnerSuccession {
+    pub room_id: uuid::Uuid,
+    pub owner_id: uuid::Uuid,
+    pub successor_id: uuid::Uuid,
+}
+
+/// A seed a member revealed for the current iteration, with the identifiers the assignment
+/// may target them by.
+#[derive(Debug)]
+pub struct SeedReveal {
+    pub seed: String,
+    pub name: String,
+    pub fingerprint: String,
+}
+
+#[derive(Debug)]
+pub struct RosterMember {
+    pub member_id: uuid::Uuid,
+    pub fingerprint: String,
+    pub name: String,
+    pub public_key: Vec<u8>,
+    pub key_algorithm: KeyAlgorithm,
+}
@@ -0,0 +1,163 @@
+use super::hub::RoomEvent;
+use super::schemas::{
+    CommitSeedRequest, OnionMessageRequest, SeedRevealRequest, VerificationRequest,
+};
+use crate::error::ErrorResponse;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Protocol versions this server can speak, oldest first.
+pub const SUPPORTED_VERSIONS: &[u16] = &[1];
+
+pub fn latest_version() -> u16 {
+    SUPPORTED_VERSIONS[SUPPORTED_VERSIONS.len() - 1]
+}
+
+pub fn is_supported(version: u16) -> bool {
+    SUPPORTED_VERSIONS.contains(&version)
+}
+
+/// Close codes the server uses when it ends a connection, from the application range 4000-4999.
+pub mod close_code {
+    /// The server is shutting down, the client should reconnect to another instance.
+    pub const GOING_AWAY: u16 = 1001;
+    /// The client stopped answering pings and sent nothing for the idle timeout.
+    pub const IDLE_TIMEOUT: u16 = 4000;
+    /// The member's session was logged out.
+    pub const LOGGED_OUT: u16 = 4001;
+    /// The member's session expired.
+    pub const SESSION_EXPIRED: u16 = 4002;
+    /// The member left the room or was kicked from it.
+    pub const REMOVED_FROM_ROOM: u16 = 4003;
+}
+
+/// Wrapper around every frame sent over a room socket.
+///
+/// `seq` counts frames in one direction of a single connection, starting at 1.
+/// Server frames answering a client command echo that command's `seq` in `reply_to`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Envelope<T> {
+    pub version: u16,
+    pub seq: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<u64>,
+    #[serde(flatten)]
+    pub message: T,
+}
+
+/// Server → client messages.
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", content = "payload", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// First frame on every connection. `last_seq` is the newest event in the room's event log.
+    Welcome {
+        member_id: Uuid,
+        room_id: Uuid,
+        last_seq: i64,
+    },
+    Event {
+        /// Position in the room's event log, absent for transient events.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        seq: Option<i64>,
+        #[serde(flatten)]
+        event: RoomEvent,
+    },
+    Pong,
+    /// The command named by `reply_to` was applied.
+    Ack,
+    Error(ErrorResponse),
+}
+
+/// Client → server messages.
+///
+/// Protocol steps mirror the `POST /room/publish/*` and `/room/commit/seed` endpoints.
+#[derive(Deserialize)]
+#[serde(tag = "type", content = "payload", rename_all = "snake_case")]
+pub enum ClientCommand {
+    Ping,
+    PublishMessage(OnionMessageRequest),
+    PublishSeed(SeedRevealRequest),
+    PublishVerification(VerificationRequest),
+    CommitSeed(CommitSeedRequest),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    #[test]
+    fn test_server_envelope_shape() {
+        let envelope = Envelope {
+            version: 1,
+            seq: 3,
+            reply_to: None,
+            message: ServerMessage::Event {
+                seq: Some(9),
+                event: RoomEvent::OnionRoundOpened { round: 2 },
+            },
+        };
+
+        assert_eq!(
+            serde_json::to_value(&envelope).unwrap(),
+            json!({
+                "version": 1,
+                "seq": 3,
+                "type": "event",
+                "payload": { "seq": 9, "type": "onion_round_opened", "round": 2 }
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_frame_matches_http_error_body() {
+        let err = AppError::new("SOME_CODE", "Some message", StatusCode::BAD_REQUEST);
+        let envelope = Envelope {
+            version: 1,
+            seq: 1,
+            reply_to: Some(4),
+            message: ServerMessage::Error(err.into()),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&envelope).unwrap(),
+            json!({
+                "version": 1,
+                "seq": 1,
+                "reply_to": 4,
+                "type": "error",
+                "payload": { "code": "SOME_CODE", "message": "Some message" }
+            })
+        );
+    }
+
+    #[test]
+    fn test_client_envelope_parsing() {
+        let envelope: Envelope<ClientCommand> =
+            serde_json::from_str(r#"{"version": 1, "seq": 7, "type": "ping"}"#).unwrap();
+
+        assert_eq!(envelope.version, 1);
+        assert_eq!(envelope.seq, 7);
+        assert!(matches!(envelope.message, ClientCommand::Ping));
+    }
+
+    #[test]
+    fn test_client_command_payload_parsing() {
+        let envelope: Envelope<ClientCommand> = serde_json::from_str(
+            r#"{
+                "version": 1,
+                "seq": 2,
+                "type": "publish_verification",
+                "payload": { "status": "rejected", "proof": "cHJvb2Y=", "seed_hash": "abc" }
+            }"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            envelope.message,
+            ClientCommand::PublishVerification(VerificationRequest::Rejected { .. })
+        ));
+    }
+}
@@ -1,6 +1,13 @@
+use super::hub::{ROOM_EVENTS_CHANNEL, RoomEvent, RoomNotification};
 use super::models;
-use crate::features::room::models::{GamePhase, OnionRoundStatus};
+use crate::features::auth::{KeyAlgorithm, KeySuite};
+use crate::features::room::models::{
+    ConnectionSlot, ExpiredPhase, GamePhase, IterationRestart, MemberPresence, MemberRole,
+    OnionRoundStatus, OwnerSuccession, PhaseDeadlines, RoomMember, RoomSnapshot, RosterMember,
+    SeedReveal, StallPolicy,
+};
 use sqlx::PgPool;
+use sqlx::types::Json;
 use uuid::Uuid;
 
 /// Fetches a room by its ID.
@@ -11,7 +18,7 @@ pub async fn get_room_by_join_code(
     sqlx::query_as!(
         models::Room,
         r#"
-        SELECT room.id, room.max_members, (
+        SELECT room.id, room.key_suite AS "key_suite: KeySuite", room.max_members, (
             CASE
                 WHEN max_members IS NOT NULL THEN (
                     SELECT COUNT(*)
@@ -29,6 +36,12 @@ pub async fn get_room_by_join_code(
     .await
 }
 
+/// Keeps a key from joining the same room twice.
+pub const MEMBER_FINGERPRINT_CONSTRAINT: &str = "room_member_room_id_fingerprint_key";
+
+/// Keeps display names unique within a room, ignoring case.
+pub const MEMBER_NAME_CONSTRAINT: &str = "room_member_room_id_name_key";
+
 /// Creates a new room member.
 pub async fn new_room_member(
     pool: &PgPool,
@@ -36,13 +49,14 @@ pub async fn new_room_member(
     name: &str,
     fingerprint: &str,
     public_key: &[u8],
+    key_algorithm: KeyAlgorithm,
     seed_commitment: &str,
 ) -> Result<Uuid, sqlx::Error> {
     sqlx::query!(
         r#"
         WITH new_member AS (
-            INSERT INTO room_member (room_id, fingerprint, public_key, name)
-            VALUES ($1, $2, $3, $4)
+            INSERT INTO room_member (room_id, fingerprint, public_key, name, key_algorithm)
+            VALUES ($1, $2, $3, $4, $6)
             RETURNING id
         ),
         iteration AS (
@@ -60,7 +74,8 @@ pub async fn new_room_member(
         fingerprint,
         public_key,
         name,
-        seed_commitment
+        seed_commitment,
+        key_algorithm as KeyAlgorithm
     )
     .fetch_one(pool)
     .await
@@ -70,6 +85,7 @@ pub async fn new_room_member(
 /// Creates a new room and an owner for that room.
 ///
 /// Returns the ID of the newly created member (the owner).
+#[allow(clippy::too_many_arguments)]
 pub async fn new_room_and_owner(
     pool: &PgPool,
     room_name: &str,
@@ -78,13 +94,26 @@ pub async fn new_room_and_owner(
     username: &str,
     fingerprint: &str,
     public_key: &[u8],
+    key_algorithm: KeyAlgorithm,
     seed_commitment: &str,
+    deadlines: &PhaseDeadlines,
+    protocol_version: i16,
 ) -> Result<Uuid, sqlx::Error> {
     sqlx::query!(
         r#"
         WITH new_room AS (
-            INSERT INTO room (name, join_code, max_members)
-            VALUES ($1, $2, $3)
+            INSERT INTO room (
+                name,
+                join_code,
+                max_members,
+                santa_id_timeout_secs,
+                seed_reveal_timeout_secs,
+                verification_timeout_secs,
+                stall_policy,
+                key_suite,
+                protocol_version
+            )
+            VALUES ($1, $2, $3, $8, $9, $10, $11, $13, $14)
             RETURNING id
         ),
         new_iteration AS (
@@ -94,8 +123,8 @@ pub async fn new_room_and_owner(
             RETURNING id
         ),
         new_member AS (
-            INSERT INTO room_member (room_id, name, fingerprint, public_key, is_owner)
-            SELECT new_room.id, $4, $5, $6, TRUE
+            INSERT INTO room_member (room_id, name, fingerprint, public_key, role, key_algorithm)
+            SELECT new_room.id, $4, $5, $6, 'owner', $12
             FROM new_room
             RETURNING id
         )
@@ -110,7 +139,14 @@ pub async fn new_room_and_owner(
         username,
         fingerprint,
         public_key,
-        seed_commitment
+        seed_commitment,
+        deadlines.santa_id_timeout_secs,
+        deadlines.seed_reveal_timeout_secs,
+        deadlines.verification_timeout_secs,
+        deadlines.stall_policy as StallPolicy,
+        key_algorithm as KeyAlgorithm,
+        key_algorithm.suite() as KeySuite,
+        protocol_version
     )
     .fetch_one(pool)
     .await
@@ -131,35 +167,244 @@ pub async fn get_current_member_count(pool: &PgPool, room_id: Uuid) -> Result<i6
     .map(|row| row.count.unwrap_or(0))
 }
 
-pub async fn is_owner(db: &PgPool, member_id: &Uuid) -> Result<bool, sqlx::Error> {
-    sqlx::query!("SELECT is_owner FROM room_member WHERE id = $1", member_id)
-        .fetch_one(db)
-        .await
-        .map(|row| row.is_owner)
-}
-
-pub async fn start_game(db: &PgPool, member_id: &Uuid) -> Result<(), sqlx::Error> {
-    let row_count = sqlx::query!(
+/// Starts the room's first game iteration if the room has reached its member limit.
+///
+/// Returns whether the game was started.
+pub async fn start_game_if_full(db: &PgPool, room_id: &Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
         r#"
         UPDATE game_iteration
         SET started_at = NOW(), phase = 'santa_id'
         WHERE
-            room_id = (SELECT room_id FROM room_member WHERE id = $1)
+            room_id = $1
             AND iteration = 0
             AND started_at IS NULL
             AND phase = 'lobby'
+            AND (SELECT max_members FROM room WHERE id = $1) <= (
+                SELECT COUNT(*)
+                FROM room_member
+                WHERE room_member.room_id = $1
+            )
+        "#,
+        room_id
+    )
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+/// Deletes a member from a room that is still in the lobby, along with their iteration state
+/// and tokens.
+///
+/// Returns the member's room ID, or `None` if the game has already started.
+pub async fn remove_lobby_member(
+    db: &PgPool,
+    member_id: &Uuid,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM room_member
+        WHERE id = $1
+        AND EXISTS(
+            SELECT 1
+            FROM game_iteration
+            WHERE game_iteration.room_id = room_member.room_id
+            AND iteration = 0
+            AND phase = 'lobby'
+        )
+        RETURNING room_id
         "#,
         member_id
     )
+    .fetch_optional(db)
+    .await
+    .map(|row| row.map(|row| row.room_id))
+}
+
+pub async fn get_member_role(db: &PgPool, member_id: &Uuid) -> Result<MemberRole, sqlx::Error> {
+    sqlx::query!(
+        r#"SELECT role AS "role: MemberRole" FROM room_member WHERE id = $1"#,
+        member_id
+    )
+    .fetch_one(db)
+    .await
+    .map(|row| row.role)
+}
+
+/// Fetches the role of a member of the given room, `None` if they are not in that room.
+pub async fn get_member_role_in_room(
+    db: &PgPool,
+    room_id: &Uuid,
+    member_id: &Uuid,
+) -> Result<Option<MemberRole>, sqlx::Error> {
+    sqlx::query!(
+        r#"SELECT role AS "role: MemberRole" FROM room_member WHERE id = $1 AND room_id = $2"#,
+        member_id,
+        room_id
+    )
+    .fetch_optional(db)
+    .await
+    .map(|row| row.map(|row| row.role))
+}
+
+/// Gives a member of the room an organiser or participant role.
+///
+/// Returns whether the member was found. The owner's role cannot be changed this way.
+pub async fn set_member_role(
+    db: &PgPool,
+    room_id: &Uuid,
+    member_id: &Uuid,
+    role: MemberRole,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE room_member
+        SET role = $3
+        WHERE id = $1
+        AND room_id = $2
+        AND role <> 'owner'
+        "#,
+        member_id,
+        room_id,
+        role as MemberRole
+    )
     .execute(db)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+/// Makes another member of the owner's room the owner, the previous owner becomes an organiser.
+///
+/// Returns whether ownership changed hands, which it does not if `owner_id` is no longer the
+/// owner or `member_id` is not in their room.
+pub async fn transfer_ownership(
+    db: &PgPool,
+    owner_id: &Uuid,
+    member_id: &Uuid,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    // demote first, a room may only have one owner at a time
+    let demoted = sqlx::query!(
+        r#"
+        UPDATE room_member
+        SET role = 'organiser'
+        WHERE id = $1
+        AND role = 'owner'
+        "#,
+        owner_id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    let promoted = sqlx::query!(
+        r#"
+        UPDATE room_member
+        SET role = 'owner'
+        WHERE id = $2
+        AND id <> $1
+        AND room_id = (SELECT room_id FROM room_member WHERE id = $1)
+        "#,
+        owner_id,
+        member_id
+    )
+    .execute(&mut *tx)
     .await?
     .rows_affected();
 
-    if row_count == 0 {
-        return Err(sqlx::Error::RowNotFound);
+    if demoted == 0 || promoted == 0 {
+        tx.rollback().await?;
+        return Ok(false);
     }
 
-    Ok(())
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Finds owners with no live connection and no session activity within `timeout_secs`, along
+/// with the member who should take over each of their rooms.
+///
+/// The successor is the most recently active organiser, or failing that the most recently
+/// active participant. Rooms where nobody else is active keep their owner.
+pub async fn get_owner_successions(
+    db: &PgPool,
+    timeout_secs: f64,
+) -> Result<Vec<OwnerSuccession>, sqlx::Error> {
+    sqlx::query_as!(
+        OwnerSuccession,
+        r#"
+        WITH activity AS (
+            SELECT
+                member.id,
+                member.room_id,
+                member.role,
+                member.joined_at,
+                GREATEST(
+                    (
+                        SELECT MAX(last_seen_at)
+                        FROM token
+                        WHERE token.member_id = member.id
+                        AND token.type = 'session'
+                    ),
+                    (
+                        SELECT MAX(expires_at)
+                        FROM member_connection
+                        WHERE member_connection.member_id = member.id
+                    )
+                ) AS last_active_at
+            FROM room_member member
+        ),
+        member_status AS (
+            SELECT
+                activity.*,
+                COALESCE(last_active_at > NOW() - make_interval(secs => $1), FALSE) AS active
+            FROM activity
+        )
+        SELECT DISTINCT ON (owner.room_id)
+            owner.room_id AS "room_id!",
+            owner.id AS "owner_id!",
+            successor.id AS "successor_id!"
+        FROM member_status owner
+        JOIN member_status successor
+            ON successor.room_id = owner.room_id
+            AND successor.id <> owner.id
+            AND successor.active
+        WHERE owner.role = 'owner'
+        AND NOT owner.active
+        ORDER BY
+            owner.room_id,
+            successor.role DESC,
+            successor.last_active_at DESC,
+            successor.joined_at
+        "#,
+        timeout_secs
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Moves the member's room out of the lobby.
+///
+/// Returns the ID of the room that was started.
+pub async fn start_game(db: &PgPool, member_id: &Uuid) -> Result<Uuid, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE game_iteration
+        SET started_at = NOW(), phase = 'santa_id'
+        WHERE
+            room_id = (SELECT room_id FROM room_member WHERE id = $1)
+            AND iteration = 0
+            AND started_at IS NULL
+            AND phase = 'lobby'
+        RETURNING room_id
+        "#,
+        member_id
+    )
+    .fetch_optional(db)
+    .await?
+    .map(|row| row.room_id)
+    .ok_or(sqlx::Error::RowNotFound)
 }
 
 pub async fn get_game_phase_by_member(
@@ -255,6 +500,288 @@ pub async fn get_onion_round_status(
     })?
 }
 
+pub async fn get_room_snapshot(db: &PgPool, member_id: &Uuid) -> Result<RoomSnapshot, sqlx::Error> {
+    sqlx::query_as!(
+        RoomSnapshot,
+        r#"
+        SELECT
+            room.id AS room_id,
+            room.name,
+            room.join_code,
+            room.max_members,
+            room.protocol_version,
+            member.role AS "role: MemberRole",
+            current_iteration.iteration AS "iteration!",
+            current_iteration.phase AS "phase!: GamePhase",
+            current_iteration.phase_deadline,
+            current_iteration.stalled_at,
+            COALESCE(state.seed IS NOT NULL, FALSE) AS "has_revealed_seed!",
+            COALESCE(state.verification_status, FALSE) AS "has_verified!"
+        FROM room_member member
+        JOIN room ON room.id = member.room_id
+        JOIN LATERAL (
+            SELECT id, iteration, phase, phase_deadline, stalled_at
+            FROM game_iteration
+            WHERE game_iteration.room_id = room.id
+            ORDER BY iteration DESC
+            LIMIT 1
+        ) current_iteration ON TRUE
+        LEFT JOIN member_iteration_state state
+            ON state.member_id = member.id
+            AND state.iteration_id = current_iteration.id
+        WHERE member.id = $1
+        "#,
+        member_id
+    )
+    .fetch_one(db)
+    .await
+}
+
+pub async fn get_room_members(db: &PgPool, room_id: &Uuid) -> Result<Vec<RoomMember>, sqlx::Error> {
+    sqlx::query_as!(
+        RoomMember,
+        r#"
+        SELECT id AS member_id, name, role AS "role: MemberRole"
+        FROM room_member
+        WHERE room_id = $1
+        ORDER BY joined_at, name
+        "#,
+        room_id
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Fetches the room's members in roster order, which is by fingerprint.
+pub async fn get_room_roster(
+    db: &PgPool,
+    room_id: &Uuid,
+) -> Result<Vec<RosterMember>, sqlx::Error> {
+    sqlx::query_as!(
+        RosterMember,
+        r#"
+        SELECT id AS member_id, fingerprint, name, public_key,
+            key_algorithm AS "key_algorithm: KeyAlgorithm"
+        FROM room_member
+        WHERE room_id = $1
+        ORDER BY fingerprint COLLATE "C"
+        "#,
+        room_id
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Marks one iteration whose phase ran past its deadline as stalled and returns it.
+///
+/// Each expired phase is claimed exactly once, even with several instances checking.
+pub async fn claim_expired_phase(db: &PgPool) -> Result<Option<ExpiredPhase>, sqlx::Error> {
+    sqlx::query_as!(
+        ExpiredPhase,
+        r#"
+        UPDATE game_iteration
+        SET stalled_at = NOW()
+        FROM room
+        WHERE room.id = game_iteration.room_id
+        AND game_iteration.id = (
+            SELECT id
+            FROM game_iteration
+            WHERE phase IN ('santa_id', 'seed_reveal', 'verification')
+            AND phase_deadline < NOW()
+            AND stalled_at IS NULL
+            ORDER BY phase_deadline
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING
+            game_iteration.room_id,
+            game_iteration.id AS iteration_id,
+            game_iteration.phase AS "phase: GamePhase",
+            room.stall_policy AS "stall_policy: StallPolicy"
+        "#
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Clears the stall mark of an expired phase so it is claimed again.
+pub async fn release_expired_phase(db: &PgPool, iteration_id: &Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE game_iteration SET stalled_at = NULL WHERE id = $1",
+        iteration_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Fetches the members who have not yet submitted what the iteration's phase asks of them.
+pub async fn get_phase_non_responders(
+    db: &PgPool,
+    iteration_id: &Uuid,
+    phase: GamePhase,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        WITH iteration AS (
+            SELECT id, room_id
+            FROM game_iteration
+            WHERE id = $1
+        ),
+        current_round AS (
+            SELECT id
+            FROM onion_round
+            WHERE iteration_id = $1
+            ORDER BY round_number DESC
+            LIMIT 1
+        )
+        SELECT member.id
+        FROM room_member member
+        JOIN iteration ON member.room_id = iteration.room_id
+        LEFT JOIN member_iteration_state state
+            ON state.member_id = member.id
+            AND state.iteration_id = iteration.id
+        WHERE state.member_id IS NULL
+        OR ($2::game_phase = 'santa_id' AND NOT EXISTS(
+            SELECT 1
+            FROM onion_message message
+            JOIN current_round ON message.round_id = current_round.id
+            WHERE message.member_id = member.id
+        ))
+        OR ($2::game_phase = 'seed_reveal' AND state.seed IS NULL)
+        OR ($2::game_phase = 'verification' AND NOT state.verification_status)
+        ORDER BY member.joined_at
+        "#,
+        iteration_id,
+        phase as GamePhase
+    )
+    .fetch_all(db)
+    .await
+    .map(|rows| rows.into_iter().map(|row| row.id).collect())
+}
+
+/// Aborts the iteration if it is still in the given phase.
+///
+/// Returns whether the iteration was aborted.
+pub async fn abort_iteration(
+    db: &PgPool,
+    iteration_id: &Uuid,
+    phase: GamePhase,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE game_iteration
+        SET phase = 'aborted'
+        WHERE id = $1
+        AND phase = $2
+        "#,
+        iteration_id,
+        phase as GamePhase
+    )
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+/// Fetches the ID and phase of the room's current game iteration.
+pub async fn get_current_iteration(
+    db: &PgPool,
+    room_id: &Uuid,
+) -> Result<(Uuid, GamePhase), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT id, phase AS "phase: GamePhase"
+        FROM game_iteration
+        WHERE room_id = $1
+        ORDER BY iteration DESC
+        LIMIT 1
+        "#,
+        room_id
+    )
+    .fetch_one(db)
+    .await
+    .map(|row| (row.id, row.phase))
+}
+
+/// Aborts a running iteration, removes the given members from the room and starts a new
+/// iteration in the `santa_id` phase.
+///
+/// If the owner is among the removed members, the longest-standing remaining organiser or
+/// participant becomes the owner. Returns `None` if the iteration is no longer running.
+pub async fn evict_and_restart(
+    db: &PgPool,
+    iteration_id: &Uuid,
+    member_ids: &[Uuid],
+) -> Result<Option<IterationRestart>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let Some(aborted) = sqlx::query!(
+        r#"
+        UPDATE game_iteration
+        SET phase = 'aborted'
+        WHERE id = $1
+        AND phase IN ('santa_id', 'seed_reveal', 'verification')
+        RETURNING room_id, iteration
+        "#,
+        iteration_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        tx.rollback().await?;
+        return Ok(None);
+    };
+
+    sqlx::query!(
+        "DELETE FROM room_member WHERE room_id = $1 AND id = ANY($2)",
+        aborted.room_id,
+        member_ids
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let new_owner_id = sqlx::query!(
+        r#"
+        UPDATE room_member
+        SET role = 'owner'
+        WHERE id = (
+            SELECT id
+            FROM room_member
+            WHERE room_id = $1
+            ORDER BY role DESC, joined_at
+            LIMIT 1
+        )
+        AND NOT EXISTS(SELECT 1 FROM room_member WHERE room_id = $1 AND role = 'owner')
+        RETURNING id
+        "#,
+        aborted.room_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .map(|row| row.id);
+
+    let iteration = sqlx::query!(
+        r#"
+        INSERT INTO game_iteration (room_id, iteration, phase)
+        VALUES ($1, $2, 'santa_id')
+        RETURNING iteration
+        "#,
+        aborted.room_id,
+        aborted.iteration + 1
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .iteration;
+
+    tx.commit().await?;
+
+    Ok(Some(IterationRestart {
+        iteration,
+        new_owner_id,
+    }))
+}
+
 pub async fn create_onion_message(
     db: &PgPool,
     room_id: &Uuid,
@@ -362,6 +889,21 @@ pub async fn get_seed_commitment_for_member(
     .map(|row| row.seed_commitment)
 }
 
+/// Fetches the version of the assignment protocol the room was created with.
+pub async fn get_room_protocol_version(db: &PgPool, room_id: &Uuid) -> Result<i16, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT protocol_version
+        FROM room
+        WHERE id = $1
+        "#,
+        room_id
+    )
+    .fetch_one(db)
+    .await
+    .map(|row| row.protocol_version)
+}
+
 pub async fn get_room_id_by_member(db: &PgPool, member_id: &Uuid) -> Result<Uuid, sqlx::Error> {
     sqlx::query!(
         r#"
@@ -407,7 +949,10 @@ pub async fn reveal_seed(
     )
     .fetch_one(db)
     .await
-    .map(|row| row.remaining_users.map(|count| count as i32))
+    .map(|row| {
+        row.remaining_users
+            .and_then(|count| i32::try_from(count).ok())
+    })
 }
 
 pub async fn mark_as_verified(db: &PgPool, member_id: &Uuid) -> Result<Option<i32>, sqlx::Error> {
@@ -436,7 +981,10 @@ pub async fn mark_as_verified(db: &PgPool, member_id: &Uuid) -> Result<Option<i3
     )
     .fetch_one(db)
     .await
-    .map(|row| row.remaining_users.map(|count| count as i32))
+    .map(|row| {
+        row.remaining_users
+            .and_then(|count| i32::try_from(count).ok())
+    })
 }
 
 pub async fn mark_as_rejected_and_restart(
@@ -518,11 +1066,10 @@ pub async fn get_onion_messages(db: &PgPool, room_id: &Uuid) -> Result<Vec<Strin
     .ok_or(sqlx::Error::RowNotFound)
 }
 
-pub async fn get_seeds_and_names(
-    db: &PgPool,
-    room_id: &Uuid,
-) -> Result<(Vec<String>, Vec<String>), sqlx::Error> {
-    let data = sqlx::query!(
+/// Fetches the seeds revealed for the room's current iteration.
+pub async fn get_seed_reveals(db: &PgPool, room_id: &Uuid) -> Result<Vec<SeedReveal>, sqlx::Error> {
+    sqlx::query_as!(
+        SeedReveal,
         r#"
         WITH current_iteration AS (
             SELECT id
@@ -531,29 +1078,28 @@ pub async fn get_seeds_and_names(
             ORDER BY iteration DESC
             LIMIT 1
         )
-        SELECT seed as "seed!", name
+        SELECT seed as "seed!", name, fingerprint
         FROM member_iteration_state member_state
         JOIN current_iteration ON member_state.iteration_id = current_iteration.id
         JOIN room_member member ON member_state.member_id = member.id
         WHERE member_state.seed IS NOT NULL
             AND member.room_id = $1
-        ORDER BY name
+        ORDER BY fingerprint COLLATE "C"
         "#,
         room_id
     )
     .fetch_all(db)
-    .await?
-    .into_iter()
-    .map(|row| (row.seed, row.name))
-    .unzip();
-
-    Ok(data)
+    .await
 }
 
-pub async fn get_member_name(db: &PgPool, member_id: &Uuid) -> Result<String, sqlx::Error> {
+/// Fetches the member's display name and fingerprint.
+pub async fn get_member_name_and_fingerprint(
+    db: &PgPool,
+    member_id: &Uuid,
+) -> Result<(String, String), sqlx::Error> {
     sqlx::query!(
         r#"
-        SELECT name
+        SELECT name, fingerprint
         FROM room_member
         WHERE id = $1
         "#,
@@ -561,7 +1107,7 @@ pub async fn get_member_name(db: &PgPool, member_id: &Uuid) -> Result<String, sq
     )
     .fetch_one(db)
     .await
-    .map(|row| row.name)
+    .map(|row| (row.name, row.fingerprint))
 }
 
 pub async fn join_next_iteration(
@@ -591,3 +1137,325 @@ pub async fn join_next_iteration(
 
     Ok(())
 }
+
+/// Broadcasts a room event to every backend instance listening on [`ROOM_EVENTS_CHANNEL`].
+pub async fn notify_room_event(
+    db: &PgPool,
+    notification: &RoomNotification,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "SELECT pg_notify($1, $2::JSONB::TEXT)",
+        ROOM_EVENTS_CHANNEL,
+        Json(notification) as _
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Appends an event to the room's event log and broadcasts it on [`ROOM_EVENTS_CHANNEL`].
+///
+/// Numbering, storing and notifying happen in one statement so that notifications are delivered
+/// in sequence order.
+pub async fn record_room_event(
+    db: &PgPool,
+    room_id: &Uuid,
+    event: &RoomEvent,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        WITH next_seq AS (
+            UPDATE room
+            SET last_event_seq = last_event_seq + 1
+            WHERE id = $1
+            RETURNING last_event_seq AS seq
+        ),
+        recorded AS (
+            INSERT INTO room_event (room_id, seq, event)
+            SELECT $1, next_seq.seq, $2
+            FROM next_seq
+            RETURNING seq
+        )
+        SELECT pg_notify(
+            $3,
+            jsonb_build_object('room_id', $1::UUID, 'seq', recorded.seq, 'event', $2::JSONB)::TEXT
+        )
+        FROM recorded
+        "#,
+        room_id,
+        Json(event) as _,
+        ROOM_EVENTS_CHANNEL
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Fetches the room's latest event sequence number.
+pub async fn get_last_event_seq(db: &PgPool, room_id: &Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query!("SELECT last_event_seq FROM room WHERE id = $1", room_id)
+        .fetch_one(db)
+        .await
+        .map(|row| row.last_event_seq)
+}
+
+/// Fetches every logged event of the room after `after_seq`, oldest first.
+pub async fn get_room_events_since(
+    db: &PgPool,
+    room_id: &Uuid,
+    after_seq: i64,
+) -> Result<Vec<RoomNotification>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT seq, event AS "event: Json<RoomEvent>"
+        FROM room_event
+        WHERE room_id = $1
+        AND seq > $2
+        ORDER BY seq
+        "#,
+        room_id,
+        after_seq
+    )
+    .fetch_all(db)
+    .await
+    .map(|rows| {
+        rows.into_iter()
+            .map(|row| RoomNotification {
+                room_id: *room_id,
+                seq: Some(row.seq),
+                event: row.event.0,
+            })
+            .collect()
+    })
+}
+
+/// Records a new live connection for a member, valid for `ttl_secs` unless refreshed.
+///
+/// The connection is only opened while the member holds fewer than `max_member_connections`
+/// and their room fewer than `max_room_connections` live connections. The room row stays locked
+/// between counting and inserting, so concurrent sockets cannot both take the last slot.
+pub async fn open_member_connection(
+    db: &PgPool,
+    member_id: &Uuid,
+    ttl_secs: f64,
+    max_member_connections: i64,
+    max_room_connections: i64,
+) -> Result<ConnectionSlot, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let room_id = sqlx::query!(
+        r#"
+        SELECT room.id
+        FROM room
+        JOIN room_member member ON member.room_id = room.id
+        WHERE member.id = $1
+        FOR NO KEY UPDATE OF room
+        "#,
+        member_id
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
+
+    let counts = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE connection.member_id = $1) AS "member_connections!",
+            COUNT(*) AS "room_connections!"
+        FROM member_connection connection
+        JOIN room_member member ON connection.member_id = member.id
+        WHERE member.room_id = $2
+        AND connection.expires_at > NOW()
+        "#,
+        member_id,
+        room_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if counts.member_connections >= max_member_connections {
+        tx.rollback().await?;
+        return Ok(ConnectionSlot::MemberLimitReached);
+    }
+    if counts.room_connections >= max_room_connections {
+        tx.rollback().await?;
+        return Ok(ConnectionSlot::RoomLimitReached);
+    }
+
+    let connection_id = sqlx::query!(
+        r#"
+        INSERT INTO member_connection (member_id, expires_at)
+        VALUES ($1, NOW() + make_interval(secs => $2))
+        RETURNING id
+        "#,
+        member_id,
+        ttl_secs
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
+
+    tx.commit().await?;
+
+    Ok(ConnectionSlot::Opened {
+        connection_id,
+        open_connections: counts.member_connections + 1,
+    })
+}
+
+/// Records a heartbeat on a live connection and extends it by another `ttl_secs`.
+pub async fn refresh_member_connection(
+    db: &PgPool,
+    connection_id: &Uuid,
+    ttl_secs: f64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE member_connection
+        SET last_seen_at = NOW(), expires_at = NOW() + make_interval(secs => $2)
+        WHERE id = $1
+        "#,
+        connection_id,
+        ttl_secs
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes a live connection.
+///
+/// Returns the number of live connections the member still has.
+pub async fn close_member_connection(
+    db: &PgPool,
+    connection_id: &Uuid,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        WITH closed AS (
+            DELETE FROM member_connection
+            WHERE id = $1
+            RETURNING member_id
+        )
+        SELECT (
+            SELECT COUNT(*)
+            FROM member_connection
+            WHERE member_id = closed.member_id
+            AND id != $1
+            AND expires_at > NOW()
+        ) AS "open_connections!"
+        FROM closed
+        "#,
+        connection_id
+    )
+    .fetch_optional(db)
+    .await
+    .map(|row| row.map_or(0, |row| row.open_connections))
+}
+
+/// Deletes connections whose socket stopped sending heartbeats without closing cleanly.
+///
+/// Returns the room and member ID of every member left without a live connection.
+pub async fn delete_expired_member_connections(
+    db: &PgPool,
+) -> Result<Vec<(Uuid, Uuid)>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        WITH expired AS (
+            DELETE FROM member_connection
+            WHERE expires_at < NOW()
+            RETURNING member_id
+        )
+        SELECT DISTINCT member.room_id, member.id AS member_id
+        FROM expired
+        JOIN room_member member ON member.id = expired.member_id
+        WHERE NOT EXISTS(
+            SELECT 1
+            FROM member_connection
+            WHERE member_connection.member_id = member.id
+            AND member_connection.expires_at >= NOW()
+        )
+        "#
+    )
+    .fetch_all(db)
+    .await
+    .map(|rows| {
+        rows.into_iter()
+            .map(|row| (row.room_id, row.member_id))
+            .collect()
+    })
+}
+
+/// Deletes rooms none of whose members have been active within `ttl_secs`.
+///
+/// Only rooms that never started or whose current iteration is stuck before a result are
+/// deleted, rooms that completed a game are kept. Returns the number of rooms deleted.
+pub async fn delete_abandoned_rooms(db: &PgPool, ttl_secs: f64) -> Result<u64, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM room
+        WHERE created_at < NOW() - make_interval(secs => $1)
+        AND (
+            SELECT phase
+            FROM game_iteration
+            WHERE game_iteration.room_id = room.id
+            ORDER BY iteration DESC
+            LIMIT 1
+        ) IN ('lobby', 'santa_id', 'seed_reveal', 'verification')
+        AND NOT EXISTS(
+            SELECT 1
+            FROM game_iteration
+            WHERE game_iteration.room_id = room.id
+            AND game_iteration.phase = 'completed'
+        )
+        AND NOT EXISTS(
+            SELECT 1
+            FROM room_member member
+            LEFT JOIN token ON token.member_id = member.id
+            LEFT JOIN member_connection ON member_connection.member_id = member.id
+            WHERE member.room_id = room.id
+            AND (
+                token.last_seen_at > NOW() - make_interval(secs => $1)
+                OR member_connection.expires_at > NOW() - make_interval(secs => $1)
+            )
+        )
+        "#,
+        ttl_secs
+    )
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected())
+}
+
+pub async fn get_room_presence(
+    db: &PgPool,
+    room_id: &Uuid,
+) -> Result<Vec<MemberPresence>, sqlx::Error> {
+    sqlx::query_as!(
+        MemberPresence,
+        r#"
+        SELECT
+            member.id AS member_id,
+            member.name,
+            EXISTS(
+                SELECT 1
+                FROM member_connection
+                WHERE member_connection.member_id = member.id
+                AND member_connection.expires_at > NOW()
+            ) AS "online!",
+            GREATEST(
+                (SELECT MAX(last_seen_at) FROM token WHERE token.member_id = member.id),
+                (SELECT MAX(last_seen_at) FROM member_connection WHERE member_connection.member_id = member.id)
+            ) AS last_active_at
+        FROM room_member member
+        WHERE member.room_id = $1
+        ORDER BY member.joined_at, member.name
+        "#,
+        room_id
+    )
+    .fetch_all(db)
+    .await
+}
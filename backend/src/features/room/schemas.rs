@@ -1,3 +1,6 @@
+use super::models::{GamePhase, MemberPresence, MemberRole, RoomMember, StallPolicy};
+use super::utils::assignment_protocol::Targets;
+use crate::features::auth::KeyAlgorithm;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
@@ -10,6 +13,16 @@ pub struct CreateRoomRequest {
     pub max_players: Option<u32>,
     pub public_key: String, // DER encoded public key
     pub seed_hash: String,
+    /// Phase timeouts, the configured defaults apply to those left out.
+    #[validate(range(min = 60, max = 2_592_000))]
+    pub santa_id_timeout_secs: Option<u32>,
+    #[validate(range(min = 60, max = 2_592_000))]
+    pub seed_reveal_timeout_secs: Option<u32>,
+    #[validate(range(min = 60, max = 2_592_000))]
+    pub verification_timeout_secs: Option<u32>,
+    pub stall_policy: Option<StallPolicy>,
+    /// Assignment protocol version, the latest one applies if left out.
+    pub protocol_version: Option<u16>,
 }
 
 #[derive(Serialize)]
@@ -27,6 +40,39 @@ pub struct JoinRoomRequest {
     pub seed_hash: String,
 }
 
+#[derive(Deserialize)]
+pub struct TargetMemberRequest {
+    pub member_id: uuid::Uuid,
+}
+
+#[derive(Deserialize)]
+pub struct TransferOwnershipRequest {
+    pub member_id: uuid::Uuid,
+}
+
+#[derive(Deserialize)]
+pub struct SetRoleRequest {
+    pub member_id: uuid::Uuid,
+    pub role: AssignableRole,
+}
+
+/// Roles the owner can hand out. Ownership itself moves through a transfer.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AssignableRole {
+    Organiser,
+    Participant,
+}
+
+impl From<AssignableRole> for MemberRole {
+    fn from(role: AssignableRole) -> Self {
+        match role {
+            AssignableRole::Organiser => MemberRole::Organiser,
+            AssignableRole::Participant => MemberRole::Participant,
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct OnionMessageRequest {
     pub message_content: Vec<String>,
@@ -48,3 +94,79 @@ pub enum VerificationRequest {
 pub struct CommitSeedRequest {
     pub seed_hash: String,
 }
+
+#[derive(Serialize)]
+pub struct RoomPresenceResponse {
+    pub members: Vec<MemberPresence>,
+}
+
+#[derive(Serialize)]
+pub struct RoomResponse {
+    pub room_id: uuid::Uuid,
+    pub name: String,
+    pub join_code: String,
+    pub max_members: Option<i32>,
+    /// Assignment protocol version clients derive and verify the assignment with.
+    pub protocol_version: i16,
+    pub role: MemberRole,
+    pub members: Vec<RoomMember>,
+    pub iteration: i32,
+    pub phase: GamePhase,
+    pub phase_deadline: Option<chrono::DateTime<chrono::Utc>>,
+    /// Whether the phase ran past its deadline and the room was flagged as stalled.
+    pub stalled: bool,
+    /// Only present while messages are being exchanged in the `SantaId` phase.
+    pub onion_round: Option<OnionRoundResponse>,
+    pub submitted: SubmissionStatus,
+}
+
+#[derive(Serialize)]
+pub struct OnionRoundResponse {
+    pub round: i32,
+    pub total_members: i64,
+    pub submitted_members: i64,
+    pub remaining_members: i64,
+}
+
+/// What the caller has already submitted in the current game iteration.
+#[derive(Serialize)]
+pub struct SubmissionStatus {
+    /// A message in the current onion round.
+    pub message: bool,
+    pub seed: bool,
+    pub verification: bool,
+}
+
+#[derive(Serialize)]
+pub struct ProtocolVersionsResponse {
+    /// Version new rooms use unless they ask for another.
+    pub default: u16,
+    pub versions: Vec<ProtocolVersionResponse>,
+}
+
+#[derive(Serialize)]
+pub struct ProtocolVersionResponse {
+    pub version: u16,
+    /// Whether new rooms can still be created with this version.
+    pub selectable: bool,
+    pub seed_combiner: &'static str,
+    pub assignment: &'static str,
+    pub targets: Targets,
+}
+
+#[derive(Serialize)]
+pub struct RosterResponse {
+    /// Changes whenever the roster does, also sent as the `ETag` header.
+    pub version: String,
+    /// Ordered by fingerprint, the order onion layers are built in.
+    pub members: Vec<RosterMemberResponse>,
+}
+
+#[derive(Serialize)]
+pub struct RosterMemberResponse {
+    pub member_id: uuid::Uuid,
+    pub fingerprint: String,
+    pub name: String,
+    pub public_key: String, // DER encoded public key
+    pub key_algorithm: KeyAlgorithm,
+}
@@ -1,15 +1,27 @@
-use super::errors::{ExpectedCurrent, RoomError};
+use super::errors::{ConnectionLimit, ExpectedCurrent, RoomError, UnsupportedVersion};
+use super::hub::{RoomEvent, RoomHub};
 use super::queries;
+use crate::config::{KeyPolicySettings, RoomSettings, WebsocketSettings};
 use crate::error::AppError;
 use crate::features::auth;
-use crate::features::room::models::GamePhase;
-use crate::features::room::schemas::VerificationRequest;
-use crate::features::room::utils::bijection;
+use crate::features::room::models::{
+    ConnectionSlot, ExpiredPhase, GamePhase, MemberPresence, MemberRole, PhaseDeadlines,
+    StallPolicy,
+};
+use crate::features::room::schemas::{
+    CreateRoomRequest, OnionRoundResponse, ProtocolVersionResponse, ProtocolVersionsResponse,
+    RoomResponse, RosterMemberResponse, RosterResponse, SubmissionStatus, VerificationRequest,
+};
+use crate::features::room::utils::{assignment_protocol, roster};
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
-use tracing::error;
+use tracing::{error, info};
 use uuid::Uuid;
 
+/// Fewest members a game can be played with.
+const MIN_MEMBERS: i64 = 2;
+
+#[allow(clippy::too_many_arguments)]
 pub async fn create_room(
     pool: &sqlx::PgPool,
     room_name: &str,
@@ -17,8 +29,23 @@ pub async fn create_room(
     public_key: &str,
     seed_commitment: &str,
     max_players: Option<u32>,
+    deadlines: &PhaseDeadlines,
+    protocol_version: Option<u16>,
+    key_policy: &KeyPolicySettings,
 ) -> Result<(Uuid, String), AppError> {
-    let (public_key, fingerprint) = auth::utils::cryptography::decode_public_key(public_key)?;
+    let protocol_version = protocol_version.unwrap_or_else(assignment_protocol::default_version);
+    if !assignment_protocol::is_selectable(protocol_version) {
+        return Err(
+            RoomError::UnsupportedAssignmentProtocol(UnsupportedVersion {
+                requested: protocol_version,
+                supported: assignment_protocol::SELECTABLE_VERSIONS,
+            })
+            .into(),
+        );
+    }
+
+    let (public_key, fingerprint, key_algorithm) =
+        auth::utils::cryptography::decode_public_key(public_key, key_policy)?;
 
     let room_code = auth::utils::cryptography::generate_room_code();
 
@@ -26,47 +53,79 @@ pub async fn create_room(
         pool,
         room_name,
         &room_code,
-        max_players.map(|p| p as i32),
+        max_players.map(|p| i32::try_from(p).unwrap_or(i32::MAX)),
         username,
         &fingerprint,
         &public_key,
+        key_algorithm,
         seed_commitment,
+        deadlines,
+        i16::try_from(protocol_version).map_err(|_| AppError::unknown_error())?,
     )
     .await?;
 
     Ok((user_id, room_code))
 }
 
+/// Picks the room's phase timeouts, falling back to the configured defaults.
+pub fn phase_deadlines(settings: &RoomSettings, body: &CreateRoomRequest) -> PhaseDeadlines {
+    let timeout = |requested: Option<u32>, default: Option<u32>| {
+        requested
+            .or(default)
+            .map(|secs| i32::try_from(secs).unwrap_or(i32::MAX))
+    };
+
+    PhaseDeadlines {
+        santa_id_timeout_secs: timeout(body.santa_id_timeout_secs, settings.santa_id_timeout_secs),
+        seed_reveal_timeout_secs: timeout(
+            body.seed_reveal_timeout_secs,
+            settings.seed_reveal_timeout_secs,
+        ),
+        verification_timeout_secs: timeout(
+            body.verification_timeout_secs,
+            settings.verification_timeout_secs,
+        ),
+        stall_policy: body.stall_policy.unwrap_or(StallPolicy::Flag),
+    }
+}
+
 /// Creates a new room member and returns the user ID.
 pub async fn join_room(
     pool: &sqlx::PgPool,
+    hub: &RoomHub,
     room_id: &str,
     username: &str,
     public_key: &str,
     seed_commitment: &str,
+    key_policy: &KeyPolicySettings,
 ) -> Result<Uuid, AppError> {
     let room = queries::get_room_by_join_code(pool, room_id)
         .await?
         .ok_or(RoomError::RoomNotFound)?;
 
-    let should_start_game: bool = match room.max_members {
-        Some(max_members) => {
-            let current_members = if let Some(count) = room.member_count {
-                count
-            } else {
-                queries::get_current_member_count(pool, room.id).await?
-            };
+    if let Some(max_members) = room.max_members {
+        let current_members = if let Some(count) = room.member_count {
+            count
+        } else {
+            queries::get_current_member_count(pool, room.id).await?
+        };
 
-            if current_members >= i64::from(max_members) {
-                return Err(RoomError::RoomFull.into());
-            }
-
-            (current_members + 1) == i64::from(max_members)
+        if current_members >= i64::from(max_members) {
+            return Err(RoomError::RoomFull.into());
         }
-        None => false,
-    };
+    }
 
-    let (public_key, fingerprint) = auth::utils::cryptography::decode_public_key(public_key)?;
+    let (public_key, fingerprint, key_algorithm) =
+        auth::utils::cryptography::decode_public_key(public_key, key_policy)?;
+
+    // onion layers are encrypted to every member, so all keys must share the room's suite
+    if key_algorithm.suite() != room.key_suite {
+        return Err(RoomError::IncompatibleKey(ExpectedCurrent {
+            expected: room.key_suite,
+            current: key_algorithm.suite(),
+        })
+        .into());
+    }
 
     let user_id = queries::new_room_member(
         pool,
@@ -74,35 +133,479 @@ pub async fn join_room(
         username,
         &fingerprint,
         &public_key,
+        key_algorithm,
         seed_commitment,
     )
-    .await?;
+    .await
+    .map_err(|err| match err {
+        sqlx::Error::Database(db_err)
+            if db_err.constraint() == Some(queries::MEMBER_FINGERPRINT_CONSTRAINT) =>
+        {
+            RoomError::AlreadyMember.into()
+        }
+        sqlx::Error::Database(db_err)
+            if db_err.constraint() == Some(queries::MEMBER_NAME_CONSTRAINT) =>
+        {
+            RoomError::NameTaken.into()
+        }
+        err => AppError::from(err),
+    })?;
 
-    if should_start_game {
-        start_game(pool, &user_id).await?;
-    }
+    hub.publish(
+        pool,
+        room.id,
+        RoomEvent::MemberJoined {
+            member_id: user_id,
+            name: username.to_string(),
+        },
+    )
+    .await;
+
+    start_game_if_full(pool, hub, &room.id).await?;
 
     Ok(user_id)
 }
 
-pub async fn requires_owner_permission(
+/// Removes the member from their room while it is still in the lobby.
+pub async fn leave_room(
     db: &sqlx::PgPool,
+    hub: &RoomHub,
     member_id: &Uuid,
 ) -> Result<(), AppError> {
-    if queries::is_owner(db, member_id).await? {
-        Ok(())
-    } else {
-        Err(RoomError::RequiresOwnerPermission.into())
+    if queries::get_member_role(db, member_id).await? == MemberRole::Owner {
+        return Err(RoomError::OwnerCannotLeave.into());
+    }
+
+    let room_id = remove_lobby_member(db, member_id).await?;
+    hub.publish(
+        db,
+        room_id,
+        RoomEvent::MemberLeft {
+            member_id: *member_id,
+        },
+    )
+    .await;
+
+    start_game_if_full(db, hub, &room_id).await
+}
+
+/// Removes another member from the room while it is still in the lobby.
+///
+/// Organisers may remove participants, the owner may remove anyone but themselves.
+pub async fn kick_member(
+    db: &sqlx::PgPool,
+    hub: &RoomHub,
+    actor_id: &Uuid,
+    member_id: &Uuid,
+) -> Result<(), AppError> {
+    if actor_id == member_id {
+        return Err(RoomError::CannotTargetSelf.into());
+    }
+
+    let actor_role = requires_role(db, actor_id, MemberRole::Organiser).await?;
+
+    let room_id = queries::get_room_id_by_member(db, actor_id).await?;
+    let member_role = queries::get_member_role_in_room(db, &room_id, member_id)
+        .await?
+        .ok_or(RoomError::MemberNotFound)?;
+
+    if member_role == MemberRole::Owner {
+        return Err(RoomError::OwnerCannotLeave.into());
+    } else if member_role >= actor_role {
+        return Err(RoomError::InsufficientRole(ExpectedCurrent {
+            expected: MemberRole::Owner,
+            current: actor_role,
+        })
+        .into());
     }
+
+    remove_lobby_member(db, member_id).await?;
+    hub.publish(
+        db,
+        room_id,
+        RoomEvent::MemberKicked {
+            member_id: *member_id,
+        },
+    )
+    .await;
+
+    start_game_if_full(db, hub, &room_id).await
+}
+
+/// Deletes the member if their room is in the lobby. Their sessions go with them.
+async fn remove_lobby_member(db: &sqlx::PgPool, member_id: &Uuid) -> Result<Uuid, AppError> {
+    expect_game_phase(db, member_id, GamePhase::Lobby).await?;
+
+    let Some(room_id) = queries::remove_lobby_member(db, member_id).await? else {
+        // the game started between the phase check and the removal
+        expect_game_phase(db, member_id, GamePhase::Lobby).await?;
+        return Err(AppError::unknown_error());
+    };
+
+    Ok(room_id)
 }
 
-pub async fn start_game(db: &sqlx::PgPool, member_id: &Uuid) -> Result<(), AppError> {
-    queries::start_game(db, member_id).await?;
+/// Starts the game once the room has as many members as its limit allows.
+async fn start_game_if_full(
+    db: &sqlx::PgPool,
+    hub: &RoomHub,
+    room_id: &Uuid,
+) -> Result<(), AppError> {
+    if queries::start_game_if_full(db, room_id).await? {
+        hub.publish(db, *room_id, RoomEvent::GameStarted).await;
+    }
+
+    Ok(())
+}
+
+/// Checks that the member holds at least the `required` role and returns their actual role.
+pub async fn requires_role(
+    db: &sqlx::PgPool,
+    member_id: &Uuid,
+    required: MemberRole,
+) -> Result<MemberRole, AppError> {
+    let role = queries::get_member_role(db, member_id).await?;
+    if role < required {
+        return Err(RoomError::InsufficientRole(ExpectedCurrent {
+            expected: required,
+            current: role,
+        })
+        .into());
+    }
+
+    Ok(role)
+}
+
+pub async fn transfer_ownership(
+    db: &sqlx::PgPool,
+    hub: &RoomHub,
+    owner_id: &Uuid,
+    member_id: &Uuid,
+) -> Result<(), AppError> {
+    if owner_id == member_id {
+        return Err(RoomError::CannotTargetSelf.into());
+    }
+
+    let room_id = queries::get_room_id_by_member(db, owner_id).await?;
+    if !queries::transfer_ownership(db, owner_id, member_id).await? {
+        return Err(RoomError::MemberNotFound.into());
+    }
+
+    publish_ownership_change(db, hub, room_id, *owner_id, *member_id).await;
+    Ok(())
+}
+
+/// Makes a member of the owner's room an organiser or a participant.
+pub async fn set_member_role(
+    db: &sqlx::PgPool,
+    hub: &RoomHub,
+    owner_id: &Uuid,
+    member_id: &Uuid,
+    role: MemberRole,
+) -> Result<(), AppError> {
+    if owner_id == member_id {
+        return Err(RoomError::CannotTargetSelf.into());
+    }
+
+    let room_id = queries::get_room_id_by_member(db, owner_id).await?;
+
+    if !queries::set_member_role(db, &room_id, member_id, role).await? {
+        return Err(RoomError::MemberNotFound.into());
+    }
+
+    hub.publish(
+        db,
+        room_id,
+        RoomEvent::RoleChanged {
+            member_id: *member_id,
+            role,
+        },
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Hands ownership of every room whose owner has been inactive for too long to an active member.
+pub async fn promote_inactive_owners(
+    db: &sqlx::PgPool,
+    hub: &RoomHub,
+    settings: &RoomSettings,
+) -> Result<(), AppError> {
+    let timeout_secs = f64::from(settings.owner_inactivity_timeout_secs);
+
+    for succession in queries::get_owner_successions(db, timeout_secs).await? {
+        // the owner may have come back or transferred ownership in the meantime
+        if !queries::transfer_ownership(db, &succession.owner_id, &succession.successor_id).await? {
+            continue;
+        }
+
+        info!(
+            room_id = %succession.room_id,
+            owner_id = %succession.owner_id,
+            successor_id = %succession.successor_id,
+            "Transferred ownership away from inactive owner"
+        );
+        publish_ownership_change(
+            db,
+            hub,
+            succession.room_id,
+            succession.owner_id,
+            succession.successor_id,
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+/// Removes an unresponsive member from a running game and restarts it without them.
+pub async fn evict_member(
+    db: &sqlx::PgPool,
+    hub: &RoomHub,
+    owner_id: &Uuid,
+    member_id: &Uuid,
+) -> Result<(), AppError> {
+    if owner_id == member_id {
+        return Err(RoomError::CannotTargetSelf.into());
+    }
+
+    let room_id = queries::get_room_id_by_member(db, owner_id).await?;
+    if queries::get_member_role_in_room(db, &room_id, member_id)
+        .await?
+        .is_none()
+    {
+        return Err(RoomError::MemberNotFound.into());
+    }
+
+    if !can_restart_without(db, &room_id, &[*member_id]).await? {
+        return Err(RoomError::NotEnoughMembers.into());
+    }
+
+    let (iteration_id, _) = queries::get_current_iteration(db, &room_id).await?;
+    if !evict_and_restart(db, hub, room_id, &iteration_id, &[*member_id]).await? {
+        return Err(RoomError::NothingToEvict.into());
+    }
+
+    Ok(())
+}
+
+/// Whether enough members would remain to play a game after removing `member_ids`.
+async fn can_restart_without(
+    db: &sqlx::PgPool,
+    room_id: &Uuid,
+    member_ids: &[Uuid],
+) -> Result<bool, AppError> {
+    let member_count = queries::get_current_member_count(db, *room_id).await?;
+    let evicted = i64::try_from(member_ids.len()).unwrap_or(i64::MAX);
+
+    Ok(member_count.saturating_sub(evicted) >= MIN_MEMBERS)
+}
+
+/// Aborts the iteration, evicts the members and starts a new iteration for the rest.
+///
+/// Returns `false` if the iteration was not running.
+async fn evict_and_restart(
+    db: &sqlx::PgPool,
+    hub: &RoomHub,
+    room_id: Uuid,
+    iteration_id: &Uuid,
+    member_ids: &[Uuid],
+) -> Result<bool, AppError> {
+    let Some(restart) = queries::evict_and_restart(db, iteration_id, member_ids).await? else {
+        return Ok(false);
+    };
+
+    info!(%room_id, evicted = ?member_ids, "Evicted members and restarted the game");
+
+    for member_id in member_ids {
+        hub.publish(
+            db,
+            room_id,
+            RoomEvent::MemberKicked {
+                member_id: *member_id,
+            },
+        )
+        .await;
+    }
+
+    if let Some(member_id) = restart.new_owner_id {
+        hub.publish(
+            db,
+            room_id,
+            RoomEvent::RoleChanged {
+                member_id,
+                role: MemberRole::Owner,
+            },
+        )
+        .await;
+    }
+
+    hub.publish(
+        db,
+        room_id,
+        RoomEvent::PhaseChanged {
+            phase: GamePhase::Aborted,
+        },
+    )
+    .await;
+    hub.publish(
+        db,
+        room_id,
+        RoomEvent::IterationRestarted {
+            iteration: restart.iteration,
+        },
+    )
+    .await;
+
+    Ok(true)
+}
+
+/// Applies each room's stall policy to the phases that ran past their deadline.
+///
+/// A room whose policy fails is logged and left to be claimed again on the next run, so it
+/// does not hold up the other rooms.
+pub async fn handle_expired_phases(db: &sqlx::PgPool, hub: &RoomHub) -> Result<(), AppError> {
+    let mut failed = Vec::new();
+
+    while let Some(expired) = queries::claim_expired_phase(db).await? {
+        if let Err(err) = apply_stall_policy(db, hub, &expired).await {
+            error!(err=?err, room_id=%expired.room_id, "Failed to apply stall policy");
+            failed.push(expired.iteration_id);
+        }
+    }
+
+    for iteration_id in failed {
+        queries::release_expired_phase(db, &iteration_id).await?;
+    }
+
+    Ok(())
+}
+
+async fn apply_stall_policy(
+    db: &sqlx::PgPool,
+    hub: &RoomHub,
+    expired: &ExpiredPhase,
+) -> Result<(), AppError> {
+    let non_responders =
+        queries::get_phase_non_responders(db, &expired.iteration_id, expired.phase).await?;
+
+    info!(
+        room_id = %expired.room_id,
+        phase = ?expired.phase,
+        policy = ?expired.stall_policy,
+        "Game phase ran past its deadline"
+    );
+    hub.publish(
+        db,
+        expired.room_id,
+        RoomEvent::PhaseExpired {
+            phase: expired.phase,
+            non_responders: non_responders.clone(),
+            stall_policy: expired.stall_policy,
+        },
+    )
+    .await;
+
+    let can_restart = match expired.stall_policy {
+        StallPolicy::EvictAndRestart if !non_responders.is_empty() => {
+            can_restart_without(db, &expired.room_id, &non_responders).await?
+        }
+        _ => false,
+    };
+
+    match expired.stall_policy {
+        StallPolicy::Flag => {}
+        // everyone submitted just as the deadline passed, leave the room flagged as stalled
+        // rather than evicting nobody or aborting a game that can go on
+        StallPolicy::EvictAndRestart if non_responders.is_empty() => {}
+        StallPolicy::EvictAndRestart if can_restart => {
+            evict_and_restart(
+                db,
+                hub,
+                expired.room_id,
+                &expired.iteration_id,
+                &non_responders,
+            )
+            .await?;
+        }
+        // too few members would be left to play, give up on the room instead
+        StallPolicy::Abort | StallPolicy::EvictAndRestart => {
+            // the last submission may have moved the phase on in the meantime
+            if queries::abort_iteration(db, &expired.iteration_id, expired.phase).await? {
+                hub.publish(
+                    db,
+                    expired.room_id,
+                    RoomEvent::PhaseChanged {
+                        phase: GamePhase::Aborted,
+                    },
+                )
+                .await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes connections left behind by sockets that went away without closing, for example
+/// when an instance crashed, and announces the members who are now offline.
+pub async fn reap_expired_connections(db: &sqlx::PgPool, hub: &RoomHub) -> Result<(), AppError> {
+    for (room_id, member_id) in queries::delete_expired_member_connections(db).await? {
+        hub.publish(db, room_id, RoomEvent::MemberOffline { member_id })
+            .await;
+    }
+
+    Ok(())
+}
+
+pub async fn delete_abandoned_rooms(
+    db: &sqlx::PgPool,
+    settings: &RoomSettings,
+) -> Result<(), AppError> {
+    let Some(ttl_secs) = settings.abandoned_room_ttl_secs else {
+        return Ok(());
+    };
+
+    let deleted = queries::delete_abandoned_rooms(db, f64::from(ttl_secs)).await?;
+    if deleted > 0 {
+        info!(deleted, "Deleted abandoned rooms");
+    }
+
+    Ok(())
+}
+
+async fn publish_ownership_change(
+    db: &sqlx::PgPool,
+    hub: &RoomHub,
+    room_id: Uuid,
+    previous_owner_id: Uuid,
+    owner_id: Uuid,
+) {
+    let changes = [
+        (previous_owner_id, MemberRole::Organiser),
+        (owner_id, MemberRole::Owner),
+    ];
+
+    for (member_id, role) in changes {
+        hub.publish(db, room_id, RoomEvent::RoleChanged { member_id, role })
+            .await;
+    }
+}
+
+pub async fn start_game(
+    db: &sqlx::PgPool,
+    hub: &RoomHub,
+    member_id: &Uuid,
+) -> Result<(), AppError> {
+    let room_id = queries::start_game(db, member_id).await?;
+    hub.publish(db, room_id, RoomEvent::GameStarted).await;
     Ok(())
 }
 
 pub async fn handle_onion_message(
     db: &sqlx::PgPool,
+    hub: &RoomHub,
     member_id: &Uuid,
     message_contents: &[String],
 ) -> Result<(), AppError> {
@@ -121,6 +624,7 @@ pub async fn handle_onion_message(
     if status.users_remaining == 1 {
         advance_message_round(
             db,
+            hub,
             &status.room_id,
             status.current_round,
             i32::try_from(status.total_users).map_err(|_e| AppError::unknown_error())?,
@@ -131,7 +635,12 @@ pub async fn handle_onion_message(
     Ok(())
 }
 
-pub async fn reveal_seed(db: &sqlx::PgPool, member_id: &Uuid, seed: &str) -> Result<(), AppError> {
+pub async fn reveal_seed(
+    db: &sqlx::PgPool,
+    hub: &RoomHub,
+    member_id: &Uuid,
+    seed: &str,
+) -> Result<(), AppError> {
     expect_game_phase(db, member_id, GamePhase::SeedReveal).await?;
 
     let seed_commitment = queries::get_seed_commitment_for_member(db, member_id).await?;
@@ -150,6 +659,14 @@ pub async fn reveal_seed(db: &sqlx::PgPool, member_id: &Uuid, seed: &str) -> Res
     if remaining_seed_reveals == 1 {
         let room_id = queries::get_room_id_by_member(db, member_id).await?;
         queries::set_game_phase(db, &room_id, GamePhase::Verification).await?;
+        hub.publish(
+            db,
+            room_id,
+            RoomEvent::PhaseChanged {
+                phase: GamePhase::Verification,
+            },
+        )
+        .await;
     }
 
     Ok(())
@@ -157,6 +674,7 @@ pub async fn reveal_seed(db: &sqlx::PgPool, member_id: &Uuid, seed: &str) -> Res
 
 pub async fn handle_verification(
     db: &sqlx::PgPool,
+    hub: &RoomHub,
     member_id: &Uuid,
     verification_request: &VerificationRequest,
 ) -> Result<(), AppError> {
@@ -168,7 +686,7 @@ pub async fn handle_verification(
     // if all members have accepted, set the game phase to Complete
 
     if let VerificationRequest::Rejected { proof, seed_hash } = verification_request {
-        return handle_verification_rejection(db, member_id, proof, seed_hash).await;
+        return handle_verification_rejection(db, hub, member_id, proof, seed_hash).await;
     }
 
     let remaining_verifications = queries::mark_as_verified(db, member_id)
@@ -178,6 +696,14 @@ pub async fn handle_verification(
     if remaining_verifications == 1 {
         let room_id = queries::get_room_id_by_member(db, member_id).await?;
         queries::set_game_phase(db, &room_id, GamePhase::Completed).await?;
+        hub.publish(
+            db,
+            room_id,
+            RoomEvent::PhaseChanged {
+                phase: GamePhase::Completed,
+            },
+        )
+        .await;
     }
 
     Ok(())
@@ -196,8 +722,184 @@ pub async fn join_next_iteration(
     Ok(())
 }
 
+/// Registers a live socket for the member and announces them as online if it is their first.
+///
+/// Rejects the socket if the member or their room already hold too many live connections.
+/// The connection expires after the idle timeout unless kept alive by
+/// [`refresh_member_connection`]. Returns the connection ID to hand back to
+/// [`disconnect_member`].
+pub async fn connect_member(
+    db: &sqlx::PgPool,
+    hub: &RoomHub,
+    settings: &WebsocketSettings,
+    room_id: Uuid,
+    member_id: Uuid,
+) -> Result<Uuid, AppError> {
+    let ttl_secs = f64::from(settings.idle_timeout_secs);
+    let slot = queries::open_member_connection(
+        db,
+        &member_id,
+        ttl_secs,
+        i64::from(settings.max_connections_per_member),
+        i64::from(settings.max_connections_per_room),
+    )
+    .await?;
+
+    let (connection_id, open_connections) = match slot {
+        ConnectionSlot::Opened {
+            connection_id,
+            open_connections,
+        } => (connection_id, open_connections),
+        ConnectionSlot::MemberLimitReached => {
+            return Err(RoomError::TooManyConnections(ConnectionLimit::Member {
+                limit: settings.max_connections_per_member,
+            })
+            .into());
+        }
+        ConnectionSlot::RoomLimitReached => {
+            return Err(RoomError::TooManyConnections(ConnectionLimit::Room {
+                limit: settings.max_connections_per_room,
+            })
+            .into());
+        }
+    };
+
+    if open_connections == 1 {
+        hub.publish(db, room_id, RoomEvent::MemberOnline { member_id })
+            .await;
+    }
+
+    Ok(connection_id)
+}
+
+pub async fn refresh_member_connection(
+    db: &sqlx::PgPool,
+    settings: &WebsocketSettings,
+    connection_id: &Uuid,
+) -> Result<(), AppError> {
+    let ttl_secs = f64::from(settings.idle_timeout_secs);
+    queries::refresh_member_connection(db, connection_id, ttl_secs).await?;
+    Ok(())
+}
+
+/// Removes a live socket and announces the member as offline if it was their last.
+pub async fn disconnect_member(
+    db: &sqlx::PgPool,
+    hub: &RoomHub,
+    room_id: Uuid,
+    member_id: Uuid,
+    connection_id: Uuid,
+) -> Result<(), AppError> {
+    let open_connections = queries::close_member_connection(db, &connection_id).await?;
+
+    if open_connections == 0 {
+        hub.publish(db, room_id, RoomEvent::MemberOffline { member_id })
+            .await;
+    }
+
+    Ok(())
+}
+
+pub async fn get_room_presence(
+    db: &sqlx::PgPool,
+    member_id: &Uuid,
+) -> Result<Vec<MemberPresence>, AppError> {
+    let room_id = queries::get_room_id_by_member(db, member_id).await?;
+    let presence = queries::get_room_presence(db, &room_id).await?;
+    Ok(presence)
+}
+
+pub async fn get_room(db: &sqlx::PgPool, member_id: &Uuid) -> Result<RoomResponse, AppError> {
+    let room = queries::get_room_snapshot(db, member_id).await?;
+    let members = queries::get_room_members(db, &room.room_id).await?;
+
+    let onion_round = if room.phase == GamePhase::SantaId {
+        match queries::get_onion_round_status(db, member_id).await {
+            Ok(status) => Some(status),
+            // no round has been opened yet
+            Err(sqlx::Error::RowNotFound) => None,
+            Err(err) => return Err(err.into()),
+        }
+    } else {
+        None
+    };
+
+    let submitted = SubmissionStatus {
+        message: onion_round
+            .as_ref()
+            .is_some_and(|status| status.user_has_sent_message),
+        seed: room.has_revealed_seed,
+        verification: room.has_verified,
+    };
+
+    Ok(RoomResponse {
+        room_id: room.room_id,
+        name: room.name,
+        join_code: room.join_code,
+        max_members: room.max_members,
+        protocol_version: room.protocol_version,
+        role: room.role,
+        members,
+        iteration: room.iteration,
+        phase: room.phase,
+        phase_deadline: room.phase_deadline,
+        stalled: room.stalled_at.is_some(),
+        onion_round: onion_round.map(|status| OnionRoundResponse {
+            round: status.current_round,
+            total_members: status.total_users,
+            submitted_members: status.total_users - status.users_remaining,
+            remaining_members: status.users_remaining,
+        }),
+        submitted,
+    })
+}
+
+/// Lists the assignment protocol versions this server supports.
+pub fn get_protocol_versions() -> ProtocolVersionsResponse {
+    let versions = assignment_protocol::PROTOCOLS
+        .iter()
+        .map(|protocol| ProtocolVersionResponse {
+            version: protocol.version,
+            selectable: assignment_protocol::is_selectable(protocol.version),
+            seed_combiner: protocol.seed_combiner,
+            assignment: protocol.assignment,
+            targets: protocol.targets,
+        })
+        .collect();
+
+    ProtocolVersionsResponse {
+        default: assignment_protocol::default_version(),
+        versions,
+    }
+}
+
+pub async fn get_roster(db: &sqlx::PgPool, member_id: &Uuid) -> Result<RosterResponse, AppError> {
+    let room_id = queries::get_room_id_by_member(db, member_id).await?;
+    let members = queries::get_room_roster(db, &room_id).await?;
+
+    let version = roster::roster_version(
+        members
+            .iter()
+            .map(|member| (member.fingerprint.as_str(), member.name.as_str())),
+    );
+
+    let members = members
+        .into_iter()
+        .map(|member| RosterMemberResponse {
+            member_id: member.member_id,
+            fingerprint: member.fingerprint,
+            name: member.name,
+            public_key: BASE64_STANDARD.encode(member.public_key),
+            key_algorithm: member.key_algorithm,
+        })
+        .collect();
+
+    Ok(RosterResponse { version, members })
+}
+
 async fn handle_verification_rejection(
     db: &sqlx::PgPool,
+    hub: &RoomHub,
     member_id: &Uuid,
     proof: &str,
     new_seed_commitment: &str,
@@ -215,16 +917,38 @@ async fn handle_verification_rejection(
         .into());
     }
 
-    // construct the bijection and verify self-assignment
-    let (seed_components, member_names) = queries::get_seeds_and_names(db, &room_id).await?;
-    let seed = bijection::combine_seed_components(&seed_components)
-        .map_err(|_| AppError::unknown_error())?;
+    // construct the bijection over the protocol's targets and verify self-assignment
+    let protocol_version = queries::get_room_protocol_version(db, &room_id).await?;
+    let Some(protocol) = u16::try_from(protocol_version)
+        .ok()
+        .and_then(assignment_protocol::get)
+    else {
+        error!(protocol_version, %room_id, "Room uses an unknown assignment protocol");
+        return Err(AppError::unknown_error());
+    };
 
-    let target_name = bijection::get_assignment(seed, proof, santa_ids, member_names)
+    let reveals = queries::get_seed_reveals(db, &room_id).await?;
+    let seed_components: Vec<String> = reveals.iter().map(|reveal| reveal.seed.clone()).collect();
+    let targets = reveals
+        .iter()
+        .map(|reveal| {
+            protocol
+                .targets
+                .pick(&reveal.name, &reveal.fingerprint)
+                .to_string()
+        })
+        .collect();
+
+    let target = protocol
+        .get_assignment(&seed_components, proof, santa_ids, targets)
+        .map_err(|err| {
+            error!(err = %err, %room_id, "Revealed seed is not valid base64");
+            AppError::unknown_error()
+        })?
         .ok_or(AppError::unknown_error())?;
-    let member_name = queries::get_member_name(db, member_id).await?;
+    let (name, fingerprint) = queries::get_member_name_and_fingerprint(db, member_id).await?;
 
-    if target_name != member_name {
+    if target != protocol.targets.pick(&name, &fingerprint) {
         return Err(RoomError::LiarLiarPantsOnFire(
             "rejection proof does not match the bijection seed".to_string(),
         )
@@ -233,26 +957,48 @@ async fn handle_verification_rejection(
 
     // proof is valid
     queries::mark_as_rejected_and_restart(db, member_id, proof, new_seed_commitment).await?;
+    hub.publish(
+        db,
+        room_id,
+        RoomEvent::PhaseChanged {
+            phase: GamePhase::Rejected,
+        },
+    )
+    .await;
 
     Ok(())
 }
 
 async fn advance_message_round(
     db: &sqlx::PgPool,
+    hub: &RoomHub,
     room_id: &Uuid,
     current_round: i32,
     members_in_room: i32,
 ) -> Result<(), AppError> {
     // Once N rounds have been completed - all messages should be decrypted.
     if current_round == members_in_room {
-        return queries::set_game_phase(db, room_id, GamePhase::SeedReveal)
-            .await
-            .map_err(std::convert::Into::into);
+        queries::set_game_phase(db, room_id, GamePhase::SeedReveal).await?;
+        hub.publish(
+            db,
+            *room_id,
+            RoomEvent::PhaseChanged {
+                phase: GamePhase::SeedReveal,
+            },
+        )
+        .await;
+        return Ok(());
     }
 
-    queries::new_message_round(db, room_id, current_round + 1)
-        .await
-        .map_err(std::convert::Into::into)
+    let next_round = current_round + 1;
+    queries::new_message_round(db, room_id, next_round).await?;
+    hub.publish(
+        db,
+        *room_id,
+        RoomEvent::OnionRoundOpened { round: next_round },
+    )
+    .await;
+    Ok(())
 }
 
 async fn expect_game_phase(
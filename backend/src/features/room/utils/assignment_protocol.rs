@@ -0,0 +1,179 @@
+use crate::features::room::utils::bijection::{self, Seed};
+
+/// A version of the assignment protocol: how the revealed seeds are combined, how the
+/// assignment is drawn from the combined seed, and what it assigns Santa IDs to.
+///
+/// Rooms keep the version they were created with until the game ends, so changing any of these
+/// needs a new version rather than a change to an existing one.
+pub struct AssignmentProtocol {
+    pub version: u16,
+    /// Name of the seed combination step, for clients to pick their implementation by.
+    pub seed_combiner: &'static str,
+    /// Name of the assignment step.
+    pub assignment: &'static str,
+    pub targets: Targets,
+    combine: fn(&[String]) -> Result<Seed, base64::DecodeError>,
+    assign: fn(Seed, &str, Vec<String>, Vec<String>) -> Option<String>,
+}
+
+/// Every version this server can verify, oldest first.
+pub const PROTOCOLS: &[AssignmentProtocol] = &[
+    // the original protocol, which rooms created before versions existed still verify under
+    AssignmentProtocol {
+        version: 1,
+        seed_combiner: "byte_sum",
+        assignment: "pcg32_lemire_sorted",
+        targets: Targets::Names,
+        combine: |components| bijection::combine_seed_components(components).map(Seed::State),
+        assign: bijection::get_assignment,
+    },
+    AssignmentProtocol {
+        version: 2,
+        seed_combiner: "sha256_sorted_length_prefixed",
+        assignment: "pcg32_stream_lemire_sorted",
+        targets: Targets::Fingerprints,
+        combine: bijection::hash_seed_components,
+        assign: bijection::get_assignment,
+    },
+];
+
+/// The member identifier Santa IDs are assigned to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Targets {
+    /// Display names. Rooms that started before names had to be unique may hold duplicates.
+    Names,
+    /// Key fingerprints, which are unique within a room.
+    Fingerprints,
+}
+
+impl Targets {
+    /// Picks the member's target out of their display name and fingerprint.
+    pub fn pick<'a>(self, name: &'a str, fingerprint: &'a str) -> &'a str {
+        match self {
+            Self::Names => name,
+            Self::Fingerprints => fingerprint,
+        }
+    }
+}
+
+impl AssignmentProtocol {
+    /// Finds the target assigned to `query_santa_id`.
+    ///
+    /// `targets` holds every member's target of this protocol's [`Targets`] kind.
+    pub fn get_assignment(
+        &self,
+        seed_components: &[String],
+        query_santa_id: &str,
+        santa_ids: Vec<String>,
+        targets: Vec<String>,
+    ) -> Result<Option<String>, base64::DecodeError> {
+        let seed = (self.combine)(seed_components)?;
+
+        Ok((self.assign)(seed, query_santa_id, santa_ids, targets))
+    }
+}
+
+/// Versions new rooms can be created with, oldest first. Older versions are only kept to
+/// verify the rooms still using them.
+pub const SELECTABLE_VERSIONS: &[u16] = &[2];
+
+/// The version new rooms use unless they ask for another.
+pub fn default_version() -> u16 {
+    SELECTABLE_VERSIONS[SELECTABLE_VERSIONS.len() - 1]
+}
+
+pub fn is_selectable(version: u16) -> bool {
+    SELECTABLE_VERSIONS.contains(&version)
+}
+
+pub fn get(version: u16) -> Option<&'static AssignmentProtocol> {
+    PROTOCOLS
+        .iter()
+        .find(|protocol| protocol.version == version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selectable_versions_are_registered() {
+        assert!(
+            SELECTABLE_VERSIONS
+                .iter()
+                .all(|&version| get(version).is_some())
+        );
+        assert!(is_selectable(default_version()));
+    }
+
+    #[test]
+    fn test_versions_are_unique_and_ordered() {
+        assert!(
+            PROTOCOLS
+                .windows(2)
+                .all(|pair| pair[0].version < pair[1].version)
+        );
+    }
+
+    #[test]
+    fn test_unknown_version() {
+        assert!(get(0).is_none());
+    }
+
+    #[test]
+    fn test_version_1_matches_original_assignment() {
+        // recorded with the implementation that predates protocol versions
+        let components = ["q83vEjRWeJA=", "AQIDBAUGBwg=", "/////w=="].map(String::from);
+        let santa_ids = [
+            "c2FudGEtZQ==",
+            "c2FudGEtYQ==",
+            "c2FudGEtZA==",
+            "c2FudGEtYg==",
+            "c2FudGEtYw==",
+        ]
+        .map(String::from)
+        .to_vec();
+        let names = ["Eve", "bob", "Alice", "dave", "Carol"]
+            .map(String::from)
+            .to_vec();
+        let expected = [
+            ("c2FudGEtYQ==", "dave"),
+            ("c2FudGEtYg==", "Eve"),
+            ("c2FudGEtYw==", "Alice"),
+            ("c2FudGEtZA==", "Carol"),
+            ("c2FudGEtZQ==", "bob"),
+        ];
+
+        let protocol = get(1).unwrap();
+        assert_eq!(protocol.targets, Targets::Names);
+        for (santa_id, name) in expected {
+            let target = protocol
+                .get_assignment(&components, santa_id, santa_ids.clone(), names.clone())
+                .unwrap();
+            assert_eq!(target.as_deref(), Some(name));
+        }
+    }
+
+    #[test]
+    fn test_versions_draw_differently() {
+        let components = ["AQI=".to_string(), "Aw==".to_string()];
+        let santa_ids: Vec<String> = (1..=8).map(|i| format!("santa{i}")).collect();
+        let targets: Vec<String> = (1..=8).map(|i| format!("target{i}")).collect();
+
+        let draw = |version| {
+            santa_ids
+                .iter()
+                .map(|santa_id| {
+                    get(version)
+                        .unwrap()
+                        .get_assignment(&components, santa_id, santa_ids.clone(), targets.clone())
+                        .unwrap()
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_ne!(draw(1), draw(2));
+    }
+}
@@ -1,7 +1,27 @@
 use crate::features::room::utils::pcg32::Pcg32;
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
+use sha2::{Digest, Sha256};
 
+/// Seed of the assignment RNG, combined from the seeds the members revealed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Seed {
+    /// Protocol version 1, seeds only the state and uses the default stream.
+    State(u64),
+    /// Protocol version 2, seeds both the state and the stream.
+    StateAndStream { state: u64, stream: u64 },
+}
+
+impl Seed {
+    fn rng(self) -> Pcg32 {
+        match self {
+            Self::State(state) => Pcg32::new(state),
+            Self::StateAndStream { state, stream } => Pcg32::with_stream(state, stream),
+        }
+    }
+}
+
+/// Protocol version 1: sums the bytes of all components.
 pub fn combine_seed_components(components: &[String]) -> Result<u64, base64::DecodeError> {
     let mut sum = 0u64;
 
@@ -15,18 +35,52 @@ pub fn combine_seed_components(components: &[String]) -> Result<u64, base64::Dec
     Ok(sum)
 }
 
+/// Protocol version 2: hashes the components with SHA-256.
+///
+/// The decoded components are sorted, so the order they were revealed in does not matter, and
+/// length-prefixed so that no two sets of components hash alike. The first half of the digest
+/// seeds the state and the second half the stream.
+pub fn hash_seed_components(components: &[String]) -> Result<Seed, base64::DecodeError> {
+    let mut decoded = components
+        .iter()
+        .map(|component| BASE64_STANDARD.decode(component.as_bytes()))
+        .collect::<Result<Vec<_>, _>>()?;
+    decoded.sort_unstable();
+
+    let mut hasher = Sha256::new();
+    for component in &decoded {
+        hasher.update((component.len() as u64).to_be_bytes());
+        hasher.update(component);
+    }
+    let digest = hasher.finalize();
+
+    let word = |offset: usize| {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&digest[offset..offset + 8]);
+        u64::from_be_bytes(bytes)
+    };
+
+    Ok(Seed::StateAndStream {
+        state: word(0),
+        stream: word(8),
+    })
+}
+
+/// Finds the target assigned to `query_santa_id`.
+///
+/// Both lists are sorted before drawing, so only their contents matter, not their order.
 pub fn get_assignment(
-    seed: u64,
+    seed: Seed,
     query_santa_id: &str,
     mut santa_ids: Vec<String>,
-    mut target_names: Vec<String>,
+    mut targets: Vec<String>,
 ) -> Option<String> {
     // Sort the lists to ensure deterministic behavior
     santa_ids.sort_unstable();
-    target_names.sort_unstable();
+    targets.sort_unstable();
 
     let mut available: Vec<_> = (0..santa_ids.len()).collect();
-    let mut rng = Pcg32::new(seed);
+    let mut rng = seed.rng();
 
     for santa_id in santa_ids {
         #[allow(clippy::cast_possible_truncation)]
@@ -36,7 +90,7 @@ pub fn get_assignment(
         let target_index = available.remove(pick_index as usize);
 
         if santa_id == query_santa_id {
-            return Some(target_names[target_index].clone());
+            return Some(targets[target_index].clone());
         }
     }
 
@@ -50,30 +104,57 @@ mod tests {
 
     #[test]
     fn test_deterministic_assignment() {
-        let seed = 2;
+        let seed = Seed::State(2);
         let santa_ids = vec![
             "santa1".to_string(),
             "santa2".to_string(),
             "santa3".to_string(),
             "santa4".to_string(),
         ];
-        let target_names = vec![
+        let targets = vec![
             "target1".to_string(),
             "target2".to_string(),
             "target3".to_string(),
             "target4".to_string(),
         ];
 
-        let assignment1 = get_assignment(seed, "santa1", santa_ids.clone(), target_names.clone());
+        let assignment1 = get_assignment(seed, "santa1", santa_ids.clone(), targets.clone());
         assert_eq!(assignment1, Some("target4".to_string()));
 
-        let assignment2 = get_assignment(seed, "santa2", santa_ids.clone(), target_names.clone());
+        let assignment2 = get_assignment(seed, "santa2", santa_ids.clone(), targets.clone());
         assert_eq!(assignment2, Some("target2".to_string()));
 
-        let assignment3 = get_assignment(seed, "santa3", santa_ids.clone(), target_names.clone());
+        let assignment3 = get_assignment(seed, "santa3", santa_ids.clone(), targets.clone());
         assert_eq!(assignment3, Some("target3".to_string()));
 
-        let assignment4 = get_assignment(seed, "santa4", santa_ids.clone(), target_names.clone());
+        let assignment4 = get_assignment(seed, "santa4", santa_ids.clone(), targets.clone());
         assert_eq!(assignment4, Some("target1".to_string()));
     }
+
+    #[test]
+    fn test_hash_seed_components_ignores_order() {
+        let components = ["AQI=".to_string(), "AwQ=".to_string()];
+        let reversed = [components[1].clone(), components[0].clone()];
+
+        assert_eq!(
+            hash_seed_components(&components).unwrap(),
+            hash_seed_components(&reversed).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_hash_seed_components_separates_components() {
+        // [1, 2] + [3] and [1] + [2, 3] sum alike under version 1
+        let split_late = ["AQI=".to_string(), "Aw==".to_string()];
+        let split_early = ["AQ==".to_string(), "AgM=".to_string()];
+
+        assert_eq!(
+            combine_seed_components(&split_late).unwrap(),
+            combine_seed_components(&split_early).unwrap()
+        );
+        assert_ne!(
+            hash_seed_components(&split_late).unwrap(),
+            hash_seed_components(&split_early).unwrap()
+        );
+    }
 }
@@ -1,2 +1,4 @@
+pub mod assignment_protocol;
 pub mod bijection;
 mod pcg32;
+pub mod roster;
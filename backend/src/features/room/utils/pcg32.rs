@@ -12,18 +12,34 @@ const SPARE: u32 = 27; // 64 - 32 - 5
 // PCG-XSH-RR-32
 pub struct Pcg32 {
     state: u64,
+    increment: u64,
 }
 
 impl Pcg32 {
     pub fn new(seed: u64) -> Self {
         let mut rng = Self {
             state: seed.wrapping_add(INCREMENT),
+            increment: INCREMENT,
         };
 
         rng.advance_state(); // Discard the first value to ensure better randomness
         rng
     }
 
+    /// Seeds both the state and the stream, as `pcg32_srandom_r` in the reference
+    /// implementation does.
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+
+        rng.advance_state();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.advance_state();
+        rng
+    }
+
     pub fn next_u32(&mut self) -> u32 {
         let old_state = self.state;
         self.advance_state();
@@ -55,7 +71,10 @@ impl Pcg32 {
     }
 
     fn advance_state(&mut self) {
-        self.state = self.state.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT);
+        self.state = self
+            .state
+            .wrapping_mul(MULTIPLIER)
+            .wrapping_add(self.increment);
     }
 }
 
@@ -80,6 +99,20 @@ mod tests {
         assert_eq!(numbers, expected);
     }
 
+    #[test]
+    fn test_pcg32_with_stream_matches_reference() {
+        // pcg32-demo from the reference implementation, seeded with 42u, 54u
+        let mut rng = Pcg32::with_stream(42, 54);
+
+        let numbers: Vec<u32> = (0..6).map(|_| rng.next_u32()).collect();
+
+        let expected = vec![
+            0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e,
+        ];
+
+        assert_eq!(numbers, expected);
+    }
+
     #[test]
     fn test_pcg32_gen_range() {
         let seed = 12345;
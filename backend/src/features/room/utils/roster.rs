@@ -0,0 +1,41 @@
+use crate::features::auth::utils::cryptography::sha256_hex;
+
+/// Computes a version string for a room roster.
+///
+/// Members must be given in roster order. The version changes whenever a member joins, leaves
+/// or the order changes, so clients can tell whether the keys they built an onion with are
+/// still current.
+pub fn roster_version<'a>(members: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    let mut bytes = Vec::new();
+
+    for (fingerprint, name) in members {
+        // length prefixes keep ("ab", "c") and ("a", "bc") apart
+        for field in [fingerprint, name] {
+            bytes.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(field.as_bytes());
+        }
+    }
+
+    sha256_hex(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roster_version_is_deterministic() {
+        let members = [("aa", "alice"), ("bb", "bob")];
+
+        assert_eq!(roster_version(members), roster_version(members));
+        assert_ne!(
+            roster_version(members),
+            roster_version([("bb", "bob"), ("aa", "alice")])
+        );
+    }
+
+    #[test]
+    fn test_roster_version_separates_fields() {
+        assert_ne!(roster_version([("ab", "c")]), roster_version([("a", "bc")]));
+    }
+}
@@ -1,11 +1,21 @@
+use super::errors::{RoomError, UnsupportedVersion};
+use super::hub::{RoomEvent, RoomNotification};
+use super::protocol::{self, ClientCommand, Envelope, ServerMessage, close_code};
+use super::{queries, service};
 use crate::error::AppError;
 use crate::features::auth;
 use crate::features::room::WebsocketOptions;
 use crate::state::SharedState;
-use axum::extract::ws::WebSocket;
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
 use axum::extract::{Query, State, WebSocketUpgrade};
 use axum::response::Response;
-use tracing::trace;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::{error, trace, warn};
+use uuid::Uuid;
 
 pub async fn upgrade_handler(
     ws: WebSocketUpgrade,
@@ -14,21 +24,402 @@ pub async fn upgrade_handler(
 ) -> Result<Response, AppError> {
     trace!("websocket connection request for room: {}", options.room);
 
-    auth::service::validate_websocket_token(&state.db, &options.token, &options.room).await?;
+    let version = options.protocol.unwrap_or_else(protocol::latest_version);
+    if !protocol::is_supported(version) {
+        return Err(RoomError::UnsupportedProtocolVersion(UnsupportedVersion {
+            requested: version,
+            supported: protocol::SUPPORTED_VERSIONS,
+        })
+        .into());
+    }
+
+    let member_id =
+        auth::service::peek_websocket_token(&state.db, &options.token, &options.room).await?;
+    let room_id = queries::get_room_id_by_member(&state.db, &member_id).await?;
+
+    // take a connection slot before using up the token, so a client turned away by the
+    // connection limits can retry with the same token
+    let connection_id = service::connect_member(
+        &state.db,
+        &state.room_hub,
+        &state.config.websocket,
+        room_id,
+        member_id,
+    )
+    .await?;
+
+    if let Err(err) =
+        auth::service::validate_websocket_token(&state.db, &options.token, &options.room).await
+    {
+        release_connection(&state, room_id, member_id, connection_id).await;
+        return Err(err);
+    }
 
-    Ok(ws.on_upgrade(handle_socket))
+    // subscribe before upgrading so no events are missed during the handshake
+    let events = state.room_hub.subscribe(room_id);
+    let replay_after = options.last_seq;
+
+    Ok(ws.on_upgrade(move |socket| {
+        let connection = Connection {
+            socket,
+            state,
+            version,
+            seq: 0,
+            member_id,
+            room_id,
+            last_event_seq: 0,
+        };
+        handle_socket(connection, connection_id, events, replay_after)
+    }))
 }
 
-async fn handle_socket(mut socket: WebSocket) {
-    trace!("websocket client connected");
+/// A single client socket speaking a negotiated protocol version.
+///
+/// Commands received on the socket act on behalf of `member_id`, the member the ephemeral
+/// token was issued to.
+struct Connection {
+    socket: WebSocket,
+    state: SharedState,
+    version: u16,
+    seq: u64,
+    member_id: Uuid,
+    room_id: Uuid,
+    /// Newest logged room event the client has been sent.
+    last_event_seq: i64,
+}
 
-    while let Some(msg) = socket.recv().await {
-        let Ok(msg) = msg else {
-            // client disconnected
+impl Connection {
+    async fn send(&mut self, message: ServerMessage) -> Result<(), axum::Error> {
+        self.send_frame(None, message).await
+    }
+
+    async fn reply(&mut self, reply_to: u64, message: ServerMessage) -> Result<(), axum::Error> {
+        self.send_frame(Some(reply_to), message).await
+    }
+
+    async fn send_frame(
+        &mut self,
+        reply_to: Option<u64>,
+        message: ServerMessage,
+    ) -> Result<(), axum::Error> {
+        self.seq += 1;
+        let envelope = Envelope {
+            version: self.version,
+            seq: self.seq,
+            reply_to,
+            message,
+        };
+
+        let payload = match serde_json::to_string(&envelope) {
+            Ok(payload) => payload,
+            Err(err) => {
+                error!(err=?err, "Failed to serialize websocket message");
+                return Ok(());
+            }
+        };
+
+        self.socket.send(Message::Text(payload.into())).await
+    }
+
+    /// Ends the connection with an application close code.
+    async fn close(&mut self, code: u16, reason: &'static str) {
+        let frame = CloseFrame {
+            code,
+            reason: reason.into(),
+        };
+        // the client may already be gone, nothing else to do then
+        let _ = self.socket.send(Message::Close(Some(frame))).await;
+    }
+
+    fn is_removed_by(&self, event: &RoomEvent) -> bool {
+        match event {
+            RoomEvent::MemberLeft { member_id } | RoomEvent::MemberKicked { member_id } => {
+                *member_id == self.member_id
+            }
+            _ => false,
+        }
+    }
+
+    /// Runs on every heartbeat tick.
+    ///
+    /// Returns `false` once the connection has been closed.
+    async fn heartbeat(&mut self, connection_id: &Uuid, last_activity: Instant) -> bool {
+        let settings = &self.state.config.websocket;
+        let idle_timeout = Duration::from_secs(settings.idle_timeout_secs.into());
+        if last_activity.elapsed() >= idle_timeout {
+            trace!(member_id=%self.member_id, "websocket client timed out");
+            self.close(close_code::IDLE_TIMEOUT, "idle timeout").await;
+            return false;
+        }
+
+        match auth::service::get_session_state(&self.state.db, self.member_id).await {
+            Ok(auth::SessionState::Active) => {}
+            Ok(auth::SessionState::Expired) => {
+                self.close(close_code::SESSION_EXPIRED, "session expired")
+                    .await;
+                return false;
+            }
+            Ok(auth::SessionState::LoggedOut) => {
+                self.close(close_code::LOGGED_OUT, "logged out").await;
+                return false;
+            }
+            Err(err) => {
+                error!(err=?err, member_id=%self.member_id, "Failed to check session state");
+            }
+        }
+
+        if let Err(err) =
+            service::refresh_member_connection(&self.state.db, settings, connection_id).await
+        {
+            error!(err=?err, member_id=%self.member_id, "Failed to refresh websocket connection");
+        }
+
+        self.socket
+            .send(Message::Ping(Vec::new().into()))
+            .await
+            .is_ok()
+    }
+
+    /// Sends a room event unless the client has already been sent it.
+    async fn send_event(&mut self, notification: &RoomNotification) -> Result<(), axum::Error> {
+        if let Some(seq) = notification.seq {
+            if seq <= self.last_event_seq {
+                return Ok(());
+            }
+            self.last_event_seq = seq;
+        }
+
+        self.send(ServerMessage::Event {
+            seq: notification.seq,
+            event: notification.event.clone(),
+        })
+        .await
+    }
+
+    /// Sends every logged room event after `last_event_seq`.
+    async fn replay_events(&mut self) -> Result<(), axum::Error> {
+        let history = match queries::get_room_events_since(
+            &self.state.db,
+            &self.room_id,
+            self.last_event_seq,
+        )
+        .await
+        {
+            Ok(history) => history,
+            Err(err) => {
+                error!(err=?err, room_id=%self.room_id, "Failed to load room events for replay");
+                return self.send_error(err).await;
+            }
+        };
+
+        for notification in &history {
+            self.send_event(notification).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn send_error(&mut self, err: impl Into<AppError>) -> Result<(), axum::Error> {
+        self.send(ServerMessage::Error(err.into().into())).await
+    }
+
+    /// Handles a single frame from the client.
+    async fn handle_frame(&mut self, frame: &str) -> Result<(), axum::Error> {
+        let envelope = match serde_json::from_str::<Envelope<ClientCommand>>(frame) {
+            Ok(envelope) => envelope,
+            Err(err) => {
+                return self
+                    .send_error(RoomError::InvalidSocketMessage(err.to_string()))
+                    .await;
+            }
+        };
+
+        if envelope.version != self.version {
+            let err = AppError::from(RoomError::UnsupportedProtocolVersion(UnsupportedVersion {
+                requested: envelope.version,
+                supported: protocol::SUPPORTED_VERSIONS,
+            }));
+            return self
+                .reply(envelope.seq, ServerMessage::Error(err.into()))
+                .await;
+        }
+
+        let response = match self.handle_command(envelope.message).await {
+            Ok(response) => response,
+            Err(err) => ServerMessage::Error(err.into()),
+        };
+
+        self.reply(envelope.seq, response).await
+    }
+
+    async fn handle_command(&mut self, command: ClientCommand) -> Result<ServerMessage, AppError> {
+        let db = &self.state.db;
+        let hub = &self.state.room_hub;
+        let member_id = &self.member_id;
+
+        match command {
+            ClientCommand::Ping => return Ok(ServerMessage::Pong),
+            ClientCommand::PublishMessage(body) => {
+                service::handle_onion_message(db, hub, member_id, &body.message_content).await?;
+            }
+            ClientCommand::PublishSeed(body) => {
+                service::reveal_seed(db, hub, member_id, &body.seed).await?;
+            }
+            ClientCommand::PublishVerification(body) => {
+                service::handle_verification(db, hub, member_id, &body).await?;
+            }
+            ClientCommand::CommitSeed(body) => {
+                service::join_next_iteration(db, member_id, &body.seed_hash).await?;
+            }
+        }
+
+        Ok(ServerMessage::Ack)
+    }
+}
+
+async fn handle_socket(
+    connection: Connection,
+    connection_id: Uuid,
+    events: broadcast::Receiver<Arc<RoomNotification>>,
+    replay_after: Option<i64>,
+) {
+    let state = connection.state.clone();
+    let member_id = connection.member_id;
+    let room_id = connection.room_id;
+    trace!(%member_id, "websocket client connected");
+    state.open_sockets.send_modify(|open| *open += 1);
+
+    run_socket(connection, connection_id, events, replay_after).await;
+    // the socket's receiver was dropped with it
+    state.room_hub.prune(room_id);
+
+    trace!(%member_id, "websocket client disconnected");
+    release_connection(&state, room_id, member_id, connection_id).await;
+    state.open_sockets.send_modify(|open| *open -= 1);
+}
+
+async fn release_connection(
+    state: &SharedState,
+    room_id: Uuid,
+    member_id: Uuid,
+    connection_id: Uuid,
+) {
+    if let Err(err) = service::disconnect_member(
+        &state.db,
+        &state.room_hub,
+        room_id,
+        member_id,
+        connection_id,
+    )
+    .await
+    {
+        error!(err=?err, %member_id, "Failed to unregister websocket connection");
+    }
+}
+
+async fn run_socket(
+    mut connection: Connection,
+    connection_id: Uuid,
+    mut events: broadcast::Receiver<Arc<RoomNotification>>,
+    replay_after: Option<i64>,
+) {
+    let member_id = connection.member_id;
+    let room_id = connection.room_id;
+
+    let last_seq = match queries::get_last_event_seq(&connection.state.db, &room_id).await {
+        Ok(last_seq) => last_seq,
+        Err(err) => {
+            error!(err=?err, %room_id, "Failed to load room event sequence");
             return;
+        }
+    };
+
+    if connection
+        .send(ServerMessage::Welcome {
+            member_id,
+            room_id,
+            last_seq,
+        })
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    // catch the client up before switching to live delivery
+    let replayed = match replay_after {
+        Some(after) if after < last_seq => {
+            connection.last_event_seq = after;
+            connection.replay_events().await
+        }
+        _ => Ok(()),
+    };
+    if replayed.is_err() {
+        return;
+    }
+    connection.last_event_seq = connection.last_event_seq.max(last_seq);
+
+    let ping_interval =
+        Duration::from_secs(connection.state.config.websocket.ping_interval_secs.into());
+    let mut heartbeat = tokio::time::interval_at(Instant::now() + ping_interval, ping_interval);
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_activity = Instant::now();
+    let mut shutdown = connection.state.shutdown.clone();
+
+    loop {
+        let result = tokio::select! {
+            msg = connection.socket.recv() => {
+                if matches!(msg, Some(Ok(_))) {
+                    last_activity = Instant::now();
+                }
+
+                match msg {
+                    Some(Ok(Message::Text(frame))) => connection.handle_frame(&frame).await,
+                    Some(Ok(Message::Binary(_))) => {
+                        connection
+                            .send_error(RoomError::InvalidSocketMessage(
+                                "binary frames are not supported".to_string(),
+                            ))
+                            .await
+                    }
+                    Some(Ok(Message::Ping(_) | Message::Pong(_))) => Ok(()),
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => {
+                        // client disconnected
+                        return;
+                    }
+                }
+            }
+            event = events.recv() => {
+                match event {
+                    Ok(notification) => {
+                        let result = connection.send_event(&notification).await;
+                        if connection.is_removed_by(&notification.event) {
+                            connection.close(close_code::REMOVED_FROM_ROOM, "removed from room").await;
+                            return;
+                        }
+                        result
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        // logged events can be recovered, transient ones are lost
+                        warn!(%member_id, skipped, "websocket client fell behind on room events");
+                        connection.replay_events().await
+                    }
+                    Err(RecvError::Closed) => return,
+                }
+            }
+            _ = shutdown.changed() => {
+                connection.close(close_code::GOING_AWAY, "server shutting down").await;
+                return;
+            }
+            _ = heartbeat.tick() => {
+                if !connection.heartbeat(&connection_id, last_activity).await {
+                    return;
+                }
+                Ok(())
+            }
         };
 
-        if socket.send(msg).await.is_err() {
+        if result.is_err() {
             // client disconnected
             return;
         }
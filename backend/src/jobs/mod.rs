@@ -0,0 +1,188 @@
+//! Recurring background jobs.
+//!
+//! Jobs are scheduled in the `scheduled_job` table. Before running a job an instance takes a
+//! lease on it, so with several replicas each run happens on exactly one of them. Failed runs
+//! are retried with exponential backoff.
+
+mod queries;
+
+use crate::config::JobSettings;
+use crate::error::AppError;
+use crate::state::SharedState;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+pub type JobFuture = Pin<Box<dyn Future<Output = Result<(), AppError>> + Send>>;
+
+/// A chore that runs every `interval`.
+pub struct Job {
+    /// Unique name, used as the key in the schedule.
+    pub name: &'static str,
+    pub interval: Duration,
+    pub run: fn(SharedState) -> JobFuture,
+}
+
+impl Job {
+    pub fn new(name: &'static str, interval: Duration, run: fn(SharedState) -> JobFuture) -> Self {
+        Self {
+            name,
+            interval,
+            run,
+        }
+    }
+}
+
+/// Runs the jobs as they fall due until `shutdown` is signalled.
+///
+/// Once signalled no new runs are started, and runs already in progress get
+/// `shutdown_timeout_secs` to finish.
+pub async fn run(state: SharedState, jobs: Vec<Job>, mut shutdown: watch::Receiver<bool>) {
+    let settings = state.config.jobs.clone();
+    let worker_id = Uuid::new_v4();
+
+    for job in &jobs {
+        if let Err(err) = queries::register_job(&state.db, job.name).await {
+            error!(err=?err, job = job.name, "Failed to register job");
+        }
+    }
+    info!(%worker_id, jobs = jobs.len(), "Job runner started");
+
+    let mut in_flight = JoinSet::new();
+    let mut poll = tokio::time::interval(Duration::from_secs(settings.poll_interval_secs.into()));
+
+    loop {
+        tokio::select! {
+            _ = poll.tick() => {}
+            _ = shutdown.changed() => break,
+        }
+
+        // reap finished runs so the set does not grow without bound
+        while in_flight.try_join_next().is_some() {}
+
+        for job in &jobs {
+            let attempts = match queries::claim_job(
+                &state.db,
+                job.name,
+                &worker_id,
+                f64::from(settings.lease_secs),
+            )
+            .await
+            {
+                Ok(Some(attempts)) => attempts,
+                Ok(None) => continue,
+                Err(err) => {
+                    error!(err=?err, job = job.name, "Failed to claim job");
+                    continue;
+                }
+            };
+
+            let state = state.clone();
+            let settings = settings.clone();
+            let (name, interval, run) = (job.name, job.interval, job.run);
+            in_flight.spawn(async move {
+                let result = run(state.clone()).await;
+                finish_run(
+                    &state, &settings, &worker_id, name, interval, attempts, result,
+                )
+                .await;
+            });
+        }
+    }
+
+    info!(running = in_flight.len(), "Job runner shutting down");
+    let timeout = Duration::from_secs(settings.shutdown_timeout_secs.into());
+    if tokio::time::timeout(timeout, async {
+        while in_flight.join_next().await.is_some() {}
+    })
+    .await
+    .is_err()
+    {
+        // their leases expire and another instance picks the jobs up again
+        warn!(
+            running = in_flight.len(),
+            "Abandoning jobs that did not finish in time"
+        );
+    }
+}
+
+async fn finish_run(
+    state: &SharedState,
+    settings: &JobSettings,
+    worker_id: &Uuid,
+    name: &'static str,
+    interval: Duration,
+    attempts: i32,
+    result: Result<(), AppError>,
+) {
+    let recorded = match result {
+        Ok(()) => queries::complete_job(&state.db, name, worker_id, interval.as_secs_f64()).await,
+        Err(err) => {
+            let attempts = u32::try_from(attempts).unwrap_or(0) + 1;
+            let delay = retry_delay(settings, attempts);
+            warn!(
+                err=?err,
+                job = name,
+                attempts,
+                retry_in_secs = delay.as_secs(),
+                "Job failed"
+            );
+
+            let message = format!("{}: {}", err.code, err.message);
+            queries::fail_job(&state.db, name, worker_id, delay.as_secs_f64(), &message).await
+        }
+    };
+
+    if let Err(err) = recorded {
+        error!(err=?err, job = name, "Failed to record job run");
+    }
+}
+
+/// Exponential backoff after `attempts` consecutive failures, capped at the configured maximum.
+fn retry_delay(settings: &JobSettings, attempts: u32) -> Duration {
+    let base = u64::from(settings.retry_base_delay_secs);
+    let factor = 1u64
+        .checked_shl(attempts.saturating_sub(1))
+        .unwrap_or(u64::MAX);
+    let secs = base
+        .saturating_mul(factor)
+        .min(u64::from(settings.retry_max_delay_secs));
+
+    Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> JobSettings {
+        JobSettings {
+            poll_interval_secs: 5,
+            lease_secs: 300,
+            retry_base_delay_secs: 10,
+            retry_max_delay_secs: 600,
+            shutdown_timeout_secs: 30,
+        }
+    }
+
+    #[test]
+    fn test_retry_delay_doubles() {
+        let settings = settings();
+
+        assert_eq!(retry_delay(&settings, 1), Duration::from_secs(10));
+        assert_eq!(retry_delay(&settings, 2), Duration::from_secs(20));
+        assert_eq!(retry_delay(&settings, 4), Duration::from_secs(80));
+    }
+
+    #[test]
+    fn test_retry_delay_is_capped() {
+        let settings = settings();
+
+        assert_eq!(retry_delay(&settings, 7), Duration::from_mins(10));
+        assert_eq!(retry_delay(&settings, 200), Duration::from_mins(10));
+    }
+}
@@ -0,0 +1,111 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Adds the job to the schedule if it is not there yet, due immediately.
+pub async fn register_job(db: &PgPool, name: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO scheduled_job (name)
+        VALUES ($1)
+        ON CONFLICT (name) DO NOTHING
+        "#,
+        name
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Takes the lease on a job if it is due and nobody else holds it.
+///
+/// Returns the number of consecutive failed runs before this one, or `None` if the job was
+/// not claimed.
+pub async fn claim_job(
+    db: &PgPool,
+    name: &str,
+    worker_id: &Uuid,
+    lease_secs: f64,
+) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE scheduled_job
+        SET
+            locked_until = NOW() + make_interval(secs => $3),
+            locked_by = $2,
+            last_started_at = NOW()
+        WHERE name = $1
+        AND next_run_at <= NOW()
+        AND (locked_until IS NULL OR locked_until < NOW())
+        RETURNING attempts
+        "#,
+        name,
+        worker_id,
+        lease_secs
+    )
+    .fetch_optional(db)
+    .await
+    .map(|row| row.map(|row| row.attempts))
+}
+
+/// Releases the lease after a successful run and schedules the next one.
+pub async fn complete_job(
+    db: &PgPool,
+    name: &str,
+    worker_id: &Uuid,
+    interval_secs: f64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE scheduled_job
+        SET
+            next_run_at = NOW() + make_interval(secs => $3),
+            locked_until = NULL,
+            locked_by = NULL,
+            attempts = 0,
+            last_error = NULL,
+            last_finished_at = NOW()
+        WHERE name = $1
+        AND locked_by = $2
+        "#,
+        name,
+        worker_id,
+        interval_secs
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Releases the lease after a failed run and schedules a retry after `retry_secs`.
+pub async fn fail_job(
+    db: &PgPool,
+    name: &str,
+    worker_id: &Uuid,
+    retry_secs: f64,
+    error: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE scheduled_job
+        SET
+            next_run_at = NOW() + make_interval(secs => $3),
+            locked_until = NULL,
+            locked_by = NULL,
+            attempts = attempts + 1,
+            last_error = $4,
+            last_finished_at = NOW()
+        WHERE name = $1
+        AND locked_by = $2
+        "#,
+        name,
+        worker_id,
+        retry_secs,
+        error
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
@@ -3,15 +3,21 @@ use crate::db::connect_db;
 use crate::state::{AppState, SharedState};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
 
 mod app;
 mod config;
 mod db;
 mod error;
 mod features;
+mod jobs;
 mod logging;
 mod state;
 
+/// How long open websockets get to close and release their connections on shutdown.
+const SOCKET_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let config = config::Settings::new()?;
@@ -19,9 +25,17 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::info!("Starting application in {} environment", config.env);
     let db = connect_db(&config.postgresql).await?;
-    let app_state: SharedState = Arc::new(AppState::new(db, config.clone()));
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let app_state: SharedState = Arc::new(AppState::new(db, config.clone(), shutdown_rx.clone()));
+    features::spawn_background_tasks(&app_state);
+
+    let job_runner = tokio::spawn(jobs::run(
+        app_state.clone(),
+        features::jobs(&config),
+        shutdown_rx,
+    ));
 
-    let app = create_app(app_state);
+    let app = create_app(app_state.clone());
     let addr = format!("{}:{}", config.app.bind_address, config.app.port);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
@@ -31,7 +45,59 @@ async fn main() -> anyhow::Result<()> {
         listener,
         app.into_make_service_with_connect_info::<SocketAddr>(),
     )
+    .with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        // open sockets and the job runner wind down on the same signal
+        shutdown_tx.send_replace(true);
+    })
     .await?;
 
+    // the server does not wait for upgraded connections, so wait for the sockets separately
+    let mut open_sockets = app_state.open_sockets.subscribe();
+    if tokio::time::timeout(
+        SOCKET_SHUTDOWN_TIMEOUT,
+        open_sockets.wait_for(|open| *open == 0),
+    )
+    .await
+    .is_err()
+    {
+        tracing::warn!("Websockets did not close in time");
+    }
+
+    // let in-flight jobs finish before exiting
+    job_runner.await?;
+
     Ok(())
 }
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!(err=?err, "Failed to listen for ctrl-c");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                tracing::error!(err=?err, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
+
+    tracing::info!("Shutting down");
+}
@@ -1,15 +1,28 @@
 use crate::config;
+use crate::features::room::hub::RoomHub;
 use sqlx::PgPool;
 use std::sync::Arc;
+use tokio::sync::watch;
 
 pub struct AppState {
     pub db: PgPool,
     pub config: config::Settings,
+    pub room_hub: RoomHub,
+    /// Flips to `true` once the server starts shutting down.
+    pub shutdown: watch::Receiver<bool>,
+    /// Number of websocket handlers still running, so shutdown can wait for them to clean up.
+    pub open_sockets: watch::Sender<usize>,
 }
 
 impl AppState {
-    pub fn new(db: PgPool, config: config::Settings) -> Self {
-        AppState { db, config }
+    pub fn new(db: PgPool, config: config::Settings, shutdown: watch::Receiver<bool>) -> Self {
+        AppState {
+            db,
+            config,
+            room_hub: RoomHub::new(),
+            shutdown,
+            open_sockets: watch::Sender::new(0),
+        }
     }
 }
 